@@ -0,0 +1,55 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::fs;
+
+use day03::{find_character_intersection, find_shared_items_exactly};
+
+fn load_input() -> Vec<String> {
+    fs::read_to_string("input.txt")
+        .expect("File 'input.txt' not readable.")
+        .lines()
+        .map(|line| line.to_owned())
+        .collect()
+}
+
+fn bench_halves(c: &mut Criterion) {
+    let lines = load_input();
+    let halves : Vec<Vec<String>> = lines.iter()
+        .map(|line| {
+            let (a, b) = line.split_at(line.len() / 2);
+            vec![a.to_owned(), b.to_owned()]
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("halves");
+    group.bench_function("hashset", |b| b.iter(|| {
+        for parts in &halves {
+            black_box(find_character_intersection(parts.clone()));
+        }
+    }));
+    group.bench_function("bitset", |b| b.iter(|| {
+        for parts in &halves {
+            black_box(find_shared_items_exactly(parts, 1).unwrap());
+        }
+    }));
+    group.finish();
+}
+
+fn bench_groups(c: &mut Criterion) {
+    let lines = load_input();
+
+    let mut group = c.benchmark_group("groups_of_3");
+    group.bench_function("hashset", |b| b.iter(|| {
+        for parts in lines.chunks(3) {
+            black_box(find_character_intersection(parts.to_vec()));
+        }
+    }));
+    group.bench_function("bitset", |b| b.iter(|| {
+        for parts in lines.chunks(3) {
+            black_box(find_shared_items_exactly(parts, 1).unwrap());
+        }
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, bench_halves, bench_groups);
+criterion_main!(benches);
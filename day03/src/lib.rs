@@ -0,0 +1,151 @@
+use std::collections::HashSet;
+use std::fmt;
+
+pub const LETTERS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// A rucksack held something that isn't one of the 52 item types.
+    InvalidItem(char),
+    /// The group shared a different number of items than the caller expected.
+    UnexpectedCount { expected: usize, found: Vec<char> },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidItem(c) => write!(f, "'{}' is not a valid item", c),
+            Error::UnexpectedCount { expected, found } => write!(
+                f, "expected {} shared item(s), found {} ({})",
+                expected, found.len(), found.iter().collect::<String>()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Priority of an item: a-z are 1-26, A-Z are 27-52.
+pub fn priority(item: char) -> Option<u32> {
+    match item {
+        'a'..='z' => Some(item as u32 - 'a' as u32 + 1),
+        'A'..='Z' => Some(item as u32 - 'A' as u32 + 27),
+        _ => None,
+    }
+}
+
+/// A set of item types, stored as one bit per priority.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ItemSet(u64);
+
+impl ItemSet {
+    /// Every one of the 52 item types.
+    pub const ALL: ItemSet = ItemSet(((1u64 << 52) - 1) << 1);
+
+    pub fn from_items(items: &str) -> Result<Self, Error> {
+        items.chars().try_fold(ItemSet::default(), |acc, c| {
+            let bit = priority(c).ok_or(Error::InvalidItem(c))?;
+            Ok(ItemSet(acc.0 | 1 << bit))
+        })
+    }
+
+    pub fn intersection(self, other: ItemSet) -> ItemSet {
+        ItemSet(self.0 & other.0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Items in priority order.
+    pub fn items(&self) -> Vec<char> {
+        LETTERS.chars().filter(|&c| self.0 & 1 << priority(c).unwrap() != 0).collect()
+    }
+
+    /// Sum of the priorities of every item in the set.
+    pub fn priority_sum(&self) -> u32 {
+        (1..=52).filter(|bit| self.0 & 1 << bit != 0).sum()
+    }
+}
+
+/// Items present in every one of `parts`, for a group of any size.
+pub fn find_shared_items<S: AsRef<str>>(parts: &[S]) -> Result<ItemSet, Error> {
+    parts.iter().try_fold(ItemSet::ALL, |acc, part| {
+        Ok(acc.intersection(ItemSet::from_items(part.as_ref())?))
+    })
+}
+
+/// Like `find_shared_items`, but fails unless exactly `expected` items are shared.
+pub fn find_shared_items_exactly<S: AsRef<str>>(parts: &[S], expected: usize) -> Result<ItemSet, Error> {
+    let shared = find_shared_items(parts)?;
+    if shared.len() != expected {
+        return Err(Error::UnexpectedCount { expected, found: shared.items() });
+    }
+    Ok(shared)
+}
+
+/// The original HashSet version, kept around as a baseline for the benchmarks.
+pub fn find_character_intersection(parts: Vec<String>) -> char {
+    let overlap : HashSet<char> = parts.iter()
+        .map(|x| x.chars().collect::<HashSet<char>>())
+        .fold(
+            LETTERS.chars().collect(),
+            |acc, el| acc.intersection(&el).map(|x| x.to_owned()).collect()
+        );
+
+    assert!(overlap.len() == 1);
+    overlap.iter().next().unwrap().to_owned()
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+
+    const SAMPLE: [&str; 6] = [
+        "vJrwpWtwJgWrhcsFMMfFFhFp",
+        "jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL",
+        "PmmdzqPrVvPwwTWBwg",
+        "wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn",
+        "ttgJtRGJQctTZtZT",
+        "CrZsJsPPZsGzwwsLwLmpwMDw",
+    ];
+
+    #[test]
+    fn test_one() {
+        let total : u32 = SAMPLE.iter()
+            .map(|line| {
+                let (a, b) = line.split_at(line.len() / 2);
+                find_shared_items_exactly(&[a, b], 1).unwrap().priority_sum()
+            })
+            .sum();
+        assert_eq!(total, 157);
+    }
+
+    #[test]
+    fn test_two() {
+        let total : u32 = SAMPLE.chunks(3)
+            .map(|group| find_shared_items_exactly(group, 1).unwrap().priority_sum())
+            .sum();
+        assert_eq!(total, 70);
+    }
+
+    #[test]
+    fn test_group_sizes() {
+        assert_eq!(find_shared_items(&["abcXY", "bcY", "cYb", "Ybc"]).unwrap().items(), vec!['b', 'c', 'Y']);
+        assert_eq!(find_shared_items(&["abc"]).unwrap().len(), 3);
+        assert_eq!(find_shared_items::<&str>(&[]).unwrap(), ItemSet::ALL);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            find_shared_items_exactly(&["abc", "bcd"], 1),
+            Err(Error::UnexpectedCount { expected: 1, found: vec!['b', 'c'] })
+        );
+        assert_eq!(find_shared_items(&["ab1"]), Err(Error::InvalidItem('1')));
+    }
+}
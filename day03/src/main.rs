@@ -1,32 +1,20 @@
 use std::fs::File;
-use std::collections::HashSet;
 use std::io::{self, prelude::*, BufReader};
 
-const LETTERS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
-
-fn find_character_intersection(parts: Vec<String>) -> char {
-    let overlap : HashSet<char> = parts.iter()
-        .map(|x| x.chars().collect::<HashSet<char>>())
-        .fold(
-            LETTERS.chars().collect(), 
-            |acc, el| acc.intersection(&el).map(|x| x.to_owned()).collect()
-        );
-
-    assert!(overlap.len() == 1);
-    return overlap.iter().next().unwrap().to_owned();
-}
+use day03::find_shared_items_exactly;
 
 fn part_1() {
     let file = File::open("input.txt")
         .unwrap_or_else(|_| panic!("File 'input.txt' not readable.") );
-    let reader = BufReader::new(file).lines().filter_map(|line| line.ok());
+    let reader = BufReader::new(file).lines().map_while(Result::ok);
 
-    let mut total: usize = 0;
+    let mut total: u32 = 0;
 
     for line in reader {
         let parts : (&str, &str) = line.split_at(line.len()/2);
-        let intersection = find_character_intersection(vec!(parts.0.to_owned(), parts.1.to_owned()));
-        total += LETTERS.find(intersection).unwrap() + 1;
+        let intersection = find_shared_items_exactly(&[parts.0, parts.1], 1)
+            .unwrap_or_else(|e| panic!("Bad rucksack '{}': {}", line, e));
+        total += intersection.priority_sum();
     }
 
     println!("Final score for Part 1: {}", total);
@@ -35,13 +23,14 @@ fn part_1() {
 fn part_2() {
     let  file = File::open("input.txt")
         .unwrap_or_else(|_| panic!("File 'input.txt' not readable.") );
-    let reader = BufReader::new(file).lines().filter_map(|line| line.ok());
+    let reader = BufReader::new(file).lines().map_while(Result::ok);
 
-    let mut total: usize = 0;
+    let mut total: u32 = 0;
 
     for parts in reader.collect::<Vec<String>>().chunks(3) {
-        let intersection = find_character_intersection(parts.to_vec());
-        total += LETTERS.find(intersection).unwrap() + 1;
+        let intersection = find_shared_items_exactly(parts, 1)
+            .unwrap_or_else(|e| panic!("Bad group {:?}: {}", parts, e));
+        total += intersection.priority_sum();
     }
 
     println!("Final score for Part 2: {}", total);
@@ -54,4 +43,4 @@ fn main() -> io::Result<()> {
 }
 
 // Final score for Part 1: 8202
-// Final score for Part 2: 2864
\ No newline at end of file
+// Final score for Part 2: 2864
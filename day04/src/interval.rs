@@ -0,0 +1,174 @@
+use std::cmp::{max, min, Reverse};
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+/// An inclusive range of section IDs, like `2-4`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Interval { start: u64, end: u64 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseIntervalError {
+    MissingDash(String),
+    BadNumber(ParseIntError),
+    Reversed { start: u64, end: u64 },
+    WrongAssignmentCount(usize),
+}
+
+impl fmt::Display for ParseIntervalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingDash(s) => write!(f, "'{}' is not of the form 'min-max'", s),
+            Self::BadNumber(e) => write!(f, "bad section ID: {}", e),
+            Self::Reversed { start, end } => write!(f, "interval {}-{} ends before it starts", start, end),
+            Self::WrongAssignmentCount(n) => write!(f, "expected 2 assignments per line, found {}", n),
+        }
+    }
+}
+
+impl std::error::Error for ParseIntervalError {}
+
+impl From<ParseIntError> for ParseIntervalError {
+    fn from(e: ParseIntError) -> Self {
+        Self::BadNumber(e)
+    }
+}
+
+impl Interval {
+    pub fn new(start: u64, end: u64) -> Result<Self, ParseIntervalError> {
+        if end < start {
+            return Err(ParseIntervalError::Reversed { start, end });
+        }
+        Ok(Self { start, end })
+    }
+
+    pub fn start(&self) -> u64 { self.start }
+    pub fn end(&self) -> u64 { self.end }
+
+    /// A `u128`, since `0-18446744073709551615` has one more section than a `u64` can count.
+    pub fn num_sections(&self) -> u128 {
+        (self.end - self.start) as u128 + 1
+    }
+
+    /// True if every section of `other` is also in `self`.
+    pub fn contains(&self, other: &Interval) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    pub fn overlaps(&self, other: &Interval) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    pub fn intersection(&self, other: &Interval) -> Option<Interval> {
+        self.overlaps(other).then(|| Interval {
+            start: max(self.start, other.start),
+            end: min(self.end, other.end),
+        })
+    }
+
+    /// Union of two intervals, if it is itself an interval (they overlap or are adjacent).
+    pub fn union(&self, other: &Interval) -> Option<Interval> {
+        let touching = self.start <= other.end.saturating_add(1) && other.start <= self.end.saturating_add(1);
+        touching.then(|| Interval {
+            start: min(self.start, other.start),
+            end: max(self.end, other.end),
+        })
+    }
+}
+
+impl FromStr for Interval {
+    type Err = ParseIntervalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s.trim().split_once('-')
+            .ok_or_else(|| ParseIntervalError::MissingDash(s.to_string()))?;
+        Interval::new(start.parse()?, end.parse()?)
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// Parse a line like `2-4,6-8` into its two assignments.
+pub fn parse_assignment_pair(line: &str) -> Result<(Interval, Interval), ParseIntervalError> {
+    let areas = line.split(',').map(|x| x.parse()).collect::<Result<Vec<Interval>, _>>()?;
+    match areas[..] {
+        [a, b] => Ok((a, b)),
+        _ => Err(ParseIntervalError::WrongAssignmentCount(areas.len())),
+    }
+}
+
+/// Every pair of indices into `intervals` whose intervals overlap, found with a sweep line.
+///
+/// Runs in O(n log n + k) for k overlapping pairs. Pairs are `(i, j)` with `i < j`, sorted.
+pub fn overlapping_pairs(intervals: &[Interval]) -> Vec<(usize, usize)> {
+    let mut order : Vec<usize> = (0..intervals.len()).collect();
+    order.sort_by_key(|&i| intervals[i].start);
+
+    // Intervals still open at the sweep position, keyed so the earliest end pops first.
+    let mut active : BinaryHeap<Reverse<(u64, usize)>> = BinaryHeap::new();
+    let mut pairs = Vec::new();
+
+    for i in order {
+        let current = intervals[i];
+        while let Some(&Reverse((end, _))) = active.peek() {
+            if end >= current.start { break; }
+            active.pop();
+        }
+        for &Reverse((_, j)) in active.iter() {
+            pairs.push((min(i, j), max(i, j)));
+        }
+        active.push(Reverse((current.end, i)));
+    }
+
+    pairs.sort();
+    pairs
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+
+    fn iv(s: &str) -> Interval {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_set_operations() {
+        assert!(iv("2-8").contains(&iv("3-7")));
+        assert!(!iv("3-7").contains(&iv("2-8")));
+        assert!(iv("5-7").overlaps(&iv("7-9")));
+        assert!(!iv("2-4").overlaps(&iv("6-8")));
+        assert_eq!(iv("2-6").intersection(&iv("4-8")), Some(iv("4-6")));
+        assert_eq!(iv("2-3").intersection(&iv("4-5")), None);
+        assert_eq!(iv("2-3").union(&iv("4-5")), Some(iv("2-5")));
+        assert_eq!(iv("2-3").union(&iv("5-6")), None);
+        assert_eq!(iv("2-4").num_sections(), 3);
+        assert_eq!(iv("7-7").num_sections(), 1);
+        assert_eq!(Interval::new(0, u64::MAX).unwrap().num_sections(), u64::MAX as u128 + 1);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!("12".parse::<Interval>(), Err(ParseIntervalError::MissingDash("12".to_string())));
+        assert!(matches!("1-x".parse::<Interval>(), Err(ParseIntervalError::BadNumber(_))));
+        assert_eq!("5-3".parse::<Interval>(), Err(ParseIntervalError::Reversed { start: 5, end: 3 }));
+        assert_eq!(parse_assignment_pair("1-2,3-4,5-6"), Err(ParseIntervalError::WrongAssignmentCount(3)));
+    }
+
+    #[test]
+    fn test_overlapping_pairs() {
+        let intervals : Vec<Interval> = ["2-4", "6-8", "2-3", "4-5", "5-7", "7-9"].iter().map(|s| iv(s)).collect();
+        let mut brute_force = Vec::new();
+        for i in 0..intervals.len() {
+            for j in i+1..intervals.len() {
+                if intervals[i].overlaps(&intervals[j]) { brute_force.push((i, j)); }
+            }
+        }
+        assert_eq!(overlapping_pairs(&intervals), brute_force);
+    }
+}
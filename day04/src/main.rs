@@ -1,31 +1,32 @@
+use std::env;
 use std::fs::File;
-use std::collections::HashSet;
 use std::io::{self, prelude::*, BufReader};
 
-fn day04() {
+pub mod interval;
+
+use interval::{overlapping_pairs, parse_assignment_pair, Interval};
+
+fn read_assignments() -> Vec<(Interval, Interval)> {
     let file = File::open("input.txt").expect("File 'input.txt' not readable.");
-    let reader = BufReader::new(file)
+    BufReader::new(file)
         .lines() // Get a line iterator
-        .filter_map(|line| line.ok()); // Get Strings instead of Result
+        .map_while(Result::ok) // Get Strings instead of Result
+        .enumerate()
+        .map(|(idx, line)| parse_assignment_pair(&line)
+            .unwrap_or_else(|e| panic!("Line {}: {}", idx + 1, e)))
+        .collect()
+}
 
+fn day04() {
     let mut fully_overlapping: u32 = 0;
     let mut partially_overlapping: u32 = 0;
 
-    for line in reader {
-        let areas: Vec<HashSet<u32>> = line
-            .split(",")                             // Split line into two assignments.
-            .map(|x|                                // On each line,
-                x.split("-")                        //  * split each assignment into min/max
-                .map(|i| i.parse::<u32>().unwrap()) //  * convert min and max into u32's
-                .collect::<Vec<u32>>()              // collect min/max vector
-            ).map(|a| (a[0]..=a[1]).collect()       // Create 2x HashSet<u32> with [min..=max]
-        ).collect();                                // Collect assignments into a vector
-
-        if areas[0].is_subset(&areas[1]) || areas[1].is_subset(&areas[0]) {
+    for (a, b) in read_assignments() {
+        if a.contains(&b) || b.contains(&a) {
             fully_overlapping += 1;
         }
 
-        if areas[0].intersection(&areas[1]).count() > 0 {
+        if a.overlaps(&b) {
             partially_overlapping += 1;
         }
     }
@@ -34,7 +35,23 @@ fn day04() {
     println!("Part 2: {} are partially overlapping.", partially_overlapping);
 }
 
+/// Every pair of elves, on any lines, whose sections overlap.
+fn all_pairs() {
+    // Elf 2n is the first assignment on line n+1, elf 2n+1 the second.
+    let elves : Vec<Interval> = read_assignments().into_iter().flat_map(|(a, b)| [a, b]).collect();
+    let pairs = overlapping_pairs(&elves);
+
+    for (i, j) in pairs.iter() {
+        println!("line {} elf {} ({}) overlaps line {} elf {} ({})",
+            i / 2 + 1, i % 2 + 1, elves[*i], j / 2 + 1, j % 2 + 1, elves[*j]);
+    }
+    println!("{} overlapping pairs among {} elves.", pairs.len(), elves.len());
+}
+
 fn main() -> io::Result<()> {
-    day04();
+    match env::args().nth(1).as_deref() {
+        Some("--all-pairs") => all_pairs(),
+        _ => day04(),
+    }
     Ok(())
 }
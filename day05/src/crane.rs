@@ -0,0 +1,96 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+/// One stack of labelled crates, bottom first.
+pub type Stack = VecDeque<String>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The drawing of the starting stacks didn't make sense.
+    BadDrawing { line: usize, reason: String },
+    /// The line didn't look like `move N from A to B`.
+    BadInstruction { line: usize, text: String },
+    /// The instruction named a stack that isn't on the pier (1-indexed, as in the input).
+    NoSuchStack { line: usize, stack: usize },
+    /// The instruction asked for more crates than the stack holds (1-indexed, as in the input).
+    NotEnoughCrates { line: usize, stack: usize, requested: usize, available: usize },
+    /// A crane was built that can't lift a single crate.
+    ZeroCapacity,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::BadDrawing { line, reason } =>
+                write!(f, "line {}: {}", line, reason),
+            Error::BadInstruction { line, text } =>
+                write!(f, "line {}: can't parse instruction '{}'", line, text),
+            Error::NoSuchStack { line, stack } =>
+                write!(f, "line {}: there is no stack {}", line, stack),
+            Error::NotEnoughCrates { line, stack, requested, available } =>
+                write!(f, "line {}: asked to move {} crates from stack {}, which only has {}",
+                    line, requested, stack, available),
+            Error::ZeroCapacity =>
+                write!(f, "a crane that can't lift anything isn't much use"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A model of crane, which decides what order crates land in when several are moved at once.
+pub trait Crane {
+    /// Move `quantity` crates off the top (back) of `from` onto `to`.
+    ///
+    /// The pier has already checked that `from` holds at least `quantity` crates.
+    fn move_crates(&self, from: &mut Stack, to: &mut Stack, quantity: usize);
+}
+
+/// Moves one crate at a time, so the moved crates end up reversed.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn move_crates(&self, from: &mut Stack, to: &mut Stack, quantity: usize) {
+        for _ in 0..quantity {
+            to.push_back(from.pop_back().unwrap());
+        }
+    }
+}
+
+/// Moves every crate at once, so their order is kept.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn move_crates(&self, from: &mut Stack, to: &mut Stack, quantity: usize) {
+        let idx = from.len() - quantity;
+        to.extend(from.drain(idx..));
+    }
+}
+
+/// Moves up to `capacity` crates at once, keeping the order within each lift.
+///
+/// A capacity of 1 behaves like the 9000, and an unlimited capacity like the 9001.
+#[derive(Debug, Clone, Copy)]
+pub struct CapacityLimited { capacity: usize }
+
+impl CapacityLimited {
+    pub fn new(capacity: usize) -> Result<Self, Error> {
+        if capacity == 0 {
+            return Err(Error::ZeroCapacity);
+        }
+        Ok(Self { capacity })
+    }
+}
+
+impl Crane for CapacityLimited {
+    fn move_crates(&self, from: &mut Stack, to: &mut Stack, quantity: usize) {
+        let mut remaining = quantity;
+        while remaining > 0 {
+            let lift = remaining.min(self.capacity);
+            CrateMover9001.move_crates(from, to, lift);
+            remaining -= lift;
+        }
+    }
+}
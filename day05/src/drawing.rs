@@ -0,0 +1,187 @@
+use std::fmt;
+
+use crate::crane::{Error, Stack};
+
+/// A run of non-blank characters on one line of the drawing, by column.
+struct Token {
+    start : usize,
+    end : usize, // exclusive
+    text : String
+}
+
+impl Token {
+    fn overlaps(&self, other: &Token) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+}
+
+/// Whitespace-separated words, e.g. the stack numbers.
+fn words(line: &str) -> Vec<Token> {
+    let chars : Vec<char> = line.chars().collect();
+    let mut tokens = Vec::new();
+    let mut col = 0;
+    while col < chars.len() {
+        if chars[col].is_whitespace() { col += 1; continue; }
+        let start = col;
+        while col < chars.len() && !chars[col].is_whitespace() { col += 1; }
+        tokens.push(Token { start, end: col, text: chars[start..col].iter().collect() });
+    }
+    tokens
+}
+
+/// Bracketed crates like `[Z]` or `[AB]`, with the label inside the brackets as the text.
+fn crates(line_number: usize, line: &str) -> Result<Vec<Token>, Error> {
+    let bad_drawing = |reason: String| Error::BadDrawing { line: line_number, reason };
+    let chars : Vec<char> = line.chars().collect();
+    let mut tokens = Vec::new();
+    let mut col = 0;
+    while col < chars.len() {
+        match chars[col] {
+            c if c.is_whitespace() => { col += 1; },
+            '[' => {
+                let start = col;
+                let close = chars[start..].iter().position(|&c| c == ']')
+                    .ok_or_else(|| bad_drawing(format!("crate at column {} is never closed", start + 1)))?;
+                let text : String = chars[start + 1..start + close].iter().collect();
+                if text.trim().is_empty() || text.contains('[') {
+                    return Err(bad_drawing(format!("crate at column {} has no label", start + 1)));
+                }
+                col = start + close + 1;
+                tokens.push(Token { start, end: col, text });
+            },
+            c => return Err(bad_drawing(format!("unexpected '{}' at column {}", c, col + 1)))
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse the drawing above the move list into stacks, bottom crate first.
+///
+/// The last line must be the stack number row, which tells us how many stacks there are and
+/// which columns they sit in. Numbers may be more than one digit, crate labels more than one
+/// character, and lines can have any amount of trailing whitespace. `first_line` is the line
+/// number of the drawing's first line in the input, for error messages.
+pub fn parse_drawing(drawing: &str, first_line: usize) -> Result<Vec<Stack>, Error> {
+    let lines : Vec<&str> = drawing.lines().collect();
+    let index_row = lines.iter().rposition(|l| !l.trim().is_empty())
+        .ok_or(Error::BadDrawing { line: first_line, reason: "drawing is empty".to_string() })?;
+    let index_line_number = first_line + index_row;
+
+    let columns = words(lines[index_row]);
+    for (idx, column) in columns.iter().enumerate() {
+        if column.text.parse::<usize>() != Ok(idx + 1) {
+            return Err(Error::BadDrawing {
+                line: index_line_number,
+                reason: format!("expected stack number {}, found '{}'", idx + 1, column.text)
+            });
+        }
+    }
+
+    let mut stacks : Vec<Stack> = vec![Stack::new(); columns.len()];
+
+    // Work bottom up, so a crate with nothing under it can be spotted.
+    for (height, row) in (0..index_row).rev().enumerate() {
+        let line_number = first_line + row;
+        for token in crates(line_number, lines[row])? {
+            let bad_drawing = |reason: &str| Error::BadDrawing {
+                line: line_number,
+                reason: format!("crate [{}] at column {} {}", token.text, token.start + 1, reason)
+            };
+            // A crate belongs to the stack whose number is under it, and there must be just one.
+            let under : Vec<usize> = (0..columns.len()).filter(|&idx| columns[idx].overlaps(&token)).collect();
+            let stack = match under[..] {
+                [stack] => stack,
+                [] => return Err(bad_drawing("isn't above a stack number")),
+                _ => return Err(bad_drawing(&format!("is above stacks {} and {}", under[0] + 1, under[1] + 1))),
+            };
+            match stacks[stack].len() {
+                n if n == height => stacks[stack].push_back(token.text.clone()),
+                n if n > height => return Err(bad_drawing(&format!("shares stack {} with another crate", stack + 1))),
+                _ => return Err(bad_drawing(&format!("is floating above stack {}", stack + 1)))
+            }
+        }
+    }
+
+    Ok(stacks)
+}
+
+/// Draw stacks in the puzzle's format, including the stack number row.
+///
+/// Columns widen to fit long labels or stack numbers, so `parse_drawing` can read it back.
+pub fn render_drawing(f: &mut fmt::Formatter, stacks: &[Stack]) -> fmt::Result {
+    let widths : Vec<usize> = stacks.iter().enumerate()
+        .map(|(idx, stack)| {
+            let widest_crate = stack.iter().map(|c| c.chars().count() + 2).max().unwrap_or(3);
+            widest_crate.max((idx + 1).to_string().len())
+        })
+        .collect();
+
+    let height = stacks.iter().map(|s| s.len()).max().unwrap_or(0);
+    for level in (0..height).rev() {
+        let row : Vec<String> = stacks.iter().zip(&widths)
+            .map(|(stack, &width)| match stack.get(level) {
+                Some(c) => format!("{:<width$}", format!("[{}]", c)),
+                None => " ".repeat(width)
+            })
+            .collect();
+        writeln!(f, "{}", row.join(" "))?;
+    }
+    let index : Vec<String> = widths.iter().enumerate()
+        .map(|(idx, &width)| format!("{:^width$}", idx + 1))
+        .collect();
+    write!(f, "{}", index.join(" "))
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+
+    fn labels(stacks: &[Stack]) -> Vec<String> {
+        stacks.iter().map(|s| s.iter().map(|c| c.as_str()).collect::<Vec<_>>().join(",")).collect()
+    }
+
+    #[test]
+    fn test_sample() {
+        let drawing = "    [D]\n[N] [C]    \n[Z] [M] [P]\n 1   2   3\n";
+        assert_eq!(labels(&parse_drawing(drawing, 1).unwrap()), vec!["Z,N", "M,C,D", "P"]);
+    }
+
+    #[test]
+    fn test_wide() {
+        let drawing = [
+            "                                        [K]",
+            "[A]                                     [J]       ",
+            "[B] [C] [D] [E] [F] [G] [H] [I]         [L] [MN]",
+            " 1   2   3   4   5   6   7   8   9  10  11  12",
+        ].join("\n");
+        let stacks = parse_drawing(&drawing, 1).unwrap();
+        assert_eq!(stacks.len(), 12);
+        assert_eq!(labels(&stacks[8..]), vec!["", "", "L,J,K", "MN"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse_drawing("[A]\n 1  3", 5),
+            Err(Error::BadDrawing { line: 6, reason: "expected stack number 2, found '3'".to_string() })
+        );
+        assert_eq!(
+            parse_drawing("    [A]\n[B]\n 1   2", 1),
+            Err(Error::BadDrawing { line: 1, reason: "crate [A] at column 5 is floating above stack 2".to_string() })
+        );
+        assert_eq!(
+            parse_drawing("[A] [B] [C]\n 1   2", 7),
+            Err(Error::BadDrawing { line: 7, reason: "crate [C] at column 9 isn't above a stack number".to_string() })
+        );
+        assert_eq!(
+            parse_drawing("  [A]\n 1   2", 1),
+            Err(Error::BadDrawing { line: 1, reason: "crate [A] at column 3 isn't above a stack number".to_string() })
+        );
+        assert_eq!(
+            parse_drawing("[ABCDE]\n 1   2", 1),
+            Err(Error::BadDrawing { line: 1, reason: "crate [ABCDE] at column 1 is above stacks 1 and 2".to_string() })
+        );
+        assert!(matches!(parse_drawing("[A\n 1", 1), Err(Error::BadDrawing { line: 1, .. })));
+        assert!(matches!(parse_drawing("", 1), Err(Error::BadDrawing { .. })));
+    }
+}
@@ -1,104 +1,91 @@
-use std::fs::File;
-use std::collections::VecDeque;
-use std::io::{self, prelude::*, BufReader};
-
-#[derive(Default, Debug)]
-struct MoveInstruction {
-    quantity : usize,
-    from : usize,
-    to : usize
-}
-
-impl MoveInstruction {
-    fn parse_line(line: &str) -> Self {
-        let parts : Vec<&str> = line.split(" ").collect();
-        assert!(parts.len() == 6);
-        let quantity = parts[1].parse::<usize>().unwrap();
-        // Convert to zero indexing because we're not animals.
-        let from = parts[3].parse::<usize>().unwrap() - 1;
-        let to = parts[5].parse::<usize>().unwrap() - 1;
-        MoveInstruction {quantity, from, to}
-    }
+use std::env;
+use std::fs;
+use std::io;
+use std::process;
+
+pub mod crane;
+pub mod drawing;
+pub mod pier;
+
+use crane::{CrateMover9000, CrateMover9001, Error};
+use pier::{CranePier, MoveInstruction};
+
+/// Parse the starting drawing and the list of moves, which are separated by the first blank
+/// line. Windows line endings are fine.
+fn parse_input(input: &str) -> Result<(CranePier, Vec<MoveInstruction>), Error> {
+    let lines : Vec<&str> = input.lines().collect();
+    let blank = lines.iter().position(|line| line.trim().is_empty())
+        .ok_or(Error::BadDrawing { line: lines.len(), reason: "no blank line after the drawing".to_string() })?;
+    let pier : CranePier = lines[..blank].join("\n").parse()?;
+
+    let instructions = lines.iter().enumerate().skip(blank + 1)
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| MoveInstruction::parse_line(idx + 1, line.trim()))
+        .collect::<Result<_, _>>()?;
+    Ok((pier, instructions))
 }
 
-#[derive(Default, Debug)]
-struct CranePier {
-    stacks: Vec<VecDeque<char>>
+fn read_input() -> (CranePier, Vec<MoveInstruction>) {
+    let input = fs::read_to_string("input.txt").expect("File 'input.txt' not readable.");
+    parse_input(&input).unwrap_or_else(|e| {
+        eprintln!("input.txt: {}", e);
+        process::exit(1);
+    })
 }
 
-impl CranePier {
-    fn new() -> Self {
-        Self { stacks: Vec::new() }
-    }
-
-    fn prepend_box_layer(&mut self, chars: &VecDeque<char>) {
-        // Get the letters with box contents
-        let values : Vec<&char> = chars.range(1..).step_by(4).collect();
-
-        // Ensure we have enough VecDeque's, since they didn't give us headings first... :-(
-        while self.stacks.len() < values.len() {
-            self.stacks.push(VecDeque::new());
-        }
-
-        // Now add letters onto "bottom" of VecDeques, since we're working top down
-        for idx in 0..values.len() {
-            if *values[idx] == ' ' {
-                continue;
-            } else {
-                self.stacks[idx].push_front(*values[idx]);
-            }
-        }
-    }
-
-    fn move_boxes_sequentially(&mut self, instruction: &MoveInstruction) {
-        for _ in 0..instruction.quantity {
-            let moving = self.stacks[instruction.from].pop_back().unwrap();
-            self.stacks[instruction.to].push_back(moving);
-        }
-    }
+fn day05() {
+    let (mut crane_pier_1, instructions) = read_input();
+    let mut crane_pier_2 = crane_pier_1.clone();
 
-    fn move_boxes_enmasse(&mut self, instruction: &MoveInstruction) {
-        let idx = self.stacks[instruction.from].len() - instruction.quantity;
-        let mut moving : VecDeque<char> = self.stacks[instruction.from].drain(idx..).collect();
-        self.stacks[instruction.to].append(&mut moving);
+    for instruction in instructions.iter() {
+        crane_pier_1.move_boxes(&CrateMover9000, instruction).unwrap_or_else(|e| panic!("{}", e));
+        crane_pier_2.move_boxes(&CrateMover9001, instruction).unwrap_or_else(|e| panic!("{}", e));
     }
 
-    fn top_boxes(&self) -> String {
-        return self.stacks.iter().map(|x| x.back().unwrap()).collect();
-    }
+    println!("Part 1 top boxes: '{}'", crane_pier_1.top_boxes());
+    println!("Part 2 top boxes: '{}'", crane_pier_2.top_boxes());
 }
 
-fn day05() {
-    let file = File::open("input.txt").expect("File 'input.txt' not readable.");
-    let reader = BufReader::new(file)
-        .lines() // Get a line iterator
-        .filter_map(|line| line.ok()); // Get Strings instead of Result
-
-    let mut crane_pier_1 = CranePier::new();
-    let mut crane_pier_2 = CranePier::new();
+/// Step through the moves one at a time with the 9001, showing what each changed.
+fn replay() {
+    let (mut pier, instructions) = read_input();
+    println!("{}\n", pier);
 
-    for line in reader {
-        if line.is_empty() { continue; } // skip empty line
-        let chars : VecDeque<char> = line.chars().collect();
-        if chars[1] == '1' { continue; } // skip index line
-        if chars[0] == 'm' {
-            let instruction = MoveInstruction::parse_line(&line);
-            crane_pier_1.move_boxes_sequentially(&instruction);
-            crane_pier_2.move_boxes_enmasse(&instruction);
-        } else {
-            crane_pier_1.prepend_box_layer(&chars);
-            crane_pier_2.prepend_box_layer(&chars);
+    for instruction in instructions.iter() {
+        pier.move_boxes(&CrateMover9001, instruction).unwrap_or_else(|e| panic!("{}", e));
+        for change in pier.diff(pier.step() - 1, pier.step()).unwrap() {
+            println!("step {:>3} ({}): stack {} {} -> {}",
+                pier.step(), instruction, change.stack, change.before.concat(), change.after.concat());
         }
     }
-
-    println!("Part 1 top boxes: '{}'", crane_pier_1.top_boxes());
-    println!("Part 2 top boxes: '{}'", crane_pier_2.top_boxes());
+    println!("\n{}", pier);
 }
 
 fn main() -> io::Result<()> {
-    day05();
+    match env::args().nth(1).as_deref() {
+        Some("--replay") => replay(),
+        _ => day05(),
+    }
     Ok(())
 }
 
 // Part 1 top boxes: 'SHQWSRBDL'
-// Part 2 top boxes: 'CDTQZHBRS'
\ No newline at end of file
+// Part 2 top boxes: 'CDTQZHBRS'
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_input() {
+        let input = "    [D]    \r\n[N] [C]    \r\n[Z] [M] [P]\r\n 1   2   3 \r\n  \t\r\nmove 1 from 2 to 1\r\nmove 3 from 1 to 3\r\n";
+        let (pier, instructions) = parse_input(input).unwrap();
+        assert_eq!(pier.top_boxes(), "NDP");
+        assert_eq!(instructions.len(), 2);
+
+        assert_eq!(parse_input("[A]\n 1\nmove 1 from 1 to 1").unwrap_err(),
+            Error::BadDrawing { line: 3, reason: "no blank line after the drawing".to_string() });
+        assert_eq!(parse_input("[A]\n 1\n\nmove 1 from 1 to 2\nmove 1 from 0 to 1").unwrap_err(),
+            Error::NoSuchStack { line: 5, stack: 0 });
+    }
+}
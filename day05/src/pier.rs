@@ -0,0 +1,302 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::crane::{Crane, Error, Stack};
+use crate::drawing::{parse_drawing, render_drawing};
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct MoveInstruction {
+    pub quantity : usize,
+    pub from : usize,
+    pub to : usize,
+    // Line number in the input, for error messages.
+    pub line : usize
+}
+
+impl MoveInstruction {
+    pub fn parse_line(line_number: usize, line: &str) -> Result<Self, Error> {
+        let bad_instruction = || Error::BadInstruction { line: line_number, text: line.to_string() };
+        let parts : Vec<&str> = line.split(' ').collect();
+        if parts.len() != 6 || parts[0] != "move" || parts[2] != "from" || parts[4] != "to" {
+            return Err(bad_instruction());
+        }
+        let quantity = parts[1].parse::<usize>().map_err(|_| bad_instruction())?;
+        // Convert to zero indexing because we're not animals.
+        let stack_index = |s: &str| match s.parse::<usize>() {
+            Ok(0) => Err(Error::NoSuchStack { line: line_number, stack: 0 }),
+            Ok(n) => Ok(n - 1),
+            Err(_) => Err(bad_instruction()),
+        };
+        let from = stack_index(parts[3])?;
+        let to = stack_index(parts[5])?;
+        Ok(MoveInstruction {quantity, from, to, line: line_number})
+    }
+}
+
+impl fmt::Display for MoveInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.quantity, self.from + 1, self.to + 1)
+    }
+}
+
+pub type Stacks = Vec<Stack>;
+
+/// A stack whose contents differ between two states, listed bottom to top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackDiff {
+    pub stack : usize, // 1-indexed, as in the drawing
+    pub before : Vec<String>,
+    pub after : Vec<String>
+}
+
+/// Compare two pier states stack by stack.
+pub fn diff_stacks(before: &Stacks, after: &Stacks) -> Vec<StackDiff> {
+    let empty = Stack::new();
+    (0..before.len().max(after.len()))
+        .filter_map(|idx| {
+            let a = before.get(idx).unwrap_or(&empty);
+            let b = after.get(idx).unwrap_or(&empty);
+            (a != b).then(|| StackDiff {
+                stack: idx + 1,
+                before: a.iter().cloned().collect(),
+                after: b.iter().cloned().collect()
+            })
+        })
+        .collect()
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct CranePier {
+    stacks: Stacks,
+
+    // History: states[n] is the layout after n moves, and step is where we are now.
+    states: Vec<Stacks>,
+    instructions: Vec<MoveInstruction>,
+    step: usize
+}
+
+impl CranePier {
+    pub fn from_stacks(stacks: Vec<Stack>) -> Self {
+        Self { states: vec![stacks.clone()], stacks, ..Self::default() }
+    }
+
+    /// Check that `instruction` can be carried out, without changing anything.
+    pub fn validate(&self, instruction: &MoveInstruction) -> Result<(), Error> {
+        for stack in [instruction.from, instruction.to] {
+            if stack >= self.stacks.len() {
+                return Err(Error::NoSuchStack { line: instruction.line, stack: stack + 1 });
+            }
+        }
+        let available = self.stacks[instruction.from].len();
+        if instruction.quantity > available {
+            return Err(Error::NotEnoughCrates {
+                line: instruction.line,
+                stack: instruction.from + 1,
+                requested: instruction.quantity,
+                available
+            });
+        }
+        Ok(())
+    }
+
+    pub fn move_boxes(&mut self, crane: &dyn Crane, instruction: &MoveInstruction) -> Result<(), Error> {
+        self.validate(instruction)?;
+
+        // Moving a stack onto itself leaves it unchanged, whatever the crane.
+        if instruction.from != instruction.to {
+            // Borrow both stacks mutably by temporarily taking the source out.
+            let mut from = std::mem::take(&mut self.stacks[instruction.from]);
+            crane.move_crates(&mut from, &mut self.stacks[instruction.to], instruction.quantity);
+            self.stacks[instruction.from] = from;
+        }
+
+        // Anything that had been undone is now a different future, so forget it.
+        self.states.truncate(self.step + 1);
+        self.instructions.truncate(self.step);
+        self.states.push(self.stacks.clone());
+        self.instructions.push(instruction.clone());
+        self.step += 1;
+        Ok(())
+    }
+
+    /// How many moves into the history we are.
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    pub fn num_steps(&self) -> usize {
+        self.instructions.len()
+    }
+
+    /// Layout after `step` moves, if we've got that far.
+    pub fn state_at(&self, step: usize) -> Option<&Stacks> {
+        self.states.get(step)
+    }
+
+    /// Jump to the layout after `step` moves. Returns false if there's no such step.
+    pub fn goto(&mut self, step: usize) -> bool {
+        match self.state_at(step) {
+            Some(state) => {
+                self.stacks = state.clone();
+                self.step = step;
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.step > 0 && self.goto(self.step - 1)
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.goto(self.step + 1)
+    }
+
+    /// Stacks that differ between the layouts after `a` and `b` moves.
+    pub fn diff(&self, a: usize, b: usize) -> Option<Vec<StackDiff>> {
+        Some(diff_stacks(self.state_at(a)?, self.state_at(b)?))
+    }
+
+    /// The label on top of each stack, with a space for each empty one so the rest stay in place.
+    pub fn top_boxes(&self) -> String {
+        self.stacks.iter().map(|x| x.back().map_or(" ", |x| x.as_str())).collect()
+    }
+}
+
+impl FromStr for CranePier {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from_stacks(parse_drawing(s, 1)?))
+    }
+}
+
+/// Draws the pier exactly as the puzzle input does, including the stack number row.
+impl fmt::Display for CranePier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        render_drawing(f, &self.stacks)
+    }
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+    use crate::crane::{CapacityLimited, CrateMover9000, CrateMover9001};
+
+    fn sample_pier() -> CranePier {
+        "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 ".parse().unwrap()
+    }
+
+    fn stack(labels: &str) -> Vec<String> {
+        labels.chars().map(|c| c.to_string()).collect()
+    }
+
+    const SAMPLE_MOVES: [&str; 4] = [
+        "move 1 from 2 to 1",
+        "move 3 from 1 to 3",
+        "move 2 from 2 to 1",
+        "move 1 from 1 to 2",
+    ];
+
+    fn run_sample(crane: &dyn Crane) -> String {
+        let mut pier = sample_pier();
+        for (idx, line) in SAMPLE_MOVES.iter().enumerate() {
+            let instruction = MoveInstruction::parse_line(idx + 1, line).unwrap();
+            pier.move_boxes(crane, &instruction).unwrap();
+        }
+        pier.top_boxes()
+    }
+
+    #[test]
+    fn test_cranes() {
+        assert_eq!(run_sample(&CrateMover9000), "CMZ");
+        assert_eq!(run_sample(&CrateMover9001), "MCD");
+        assert_eq!(run_sample(&CapacityLimited::new(1).unwrap()), "CMZ");
+        assert_eq!(run_sample(&CapacityLimited::new(3).unwrap()), "MCD");
+
+        let mut pier = sample_pier();
+        let instruction = MoveInstruction::parse_line(1, "move 3 from 3 to 1").unwrap();
+        pier.stacks[2] = stack("ABCDE").into();
+        pier.move_boxes(&CapacityLimited::new(2).unwrap(), &instruction).unwrap();
+        assert_eq!(Vec::from(pier.stacks[0].clone()), stack("ZNDEC"));
+        assert_eq!(CapacityLimited::new(0).unwrap_err(), Error::ZeroCapacity);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut pier = sample_pier();
+        let instruction = MoveInstruction::parse_line(7, "move 4 from 3 to 1").unwrap();
+        assert_eq!(
+            pier.move_boxes(&CrateMover9000, &instruction),
+            Err(Error::NotEnoughCrates { line: 7, stack: 3, requested: 4, available: 1 })
+        );
+        assert_eq!(pier.top_boxes(), "NDP");
+
+        let instruction = MoveInstruction::parse_line(8, "move 1 from 4 to 1").unwrap();
+        assert_eq!(pier.move_boxes(&CrateMover9001, &instruction), Err(Error::NoSuchStack { line: 8, stack: 4 }));
+        assert_eq!(MoveInstruction::parse_line(9, "move 1 from 0 to 1").unwrap_err(), Error::NoSuchStack { line: 9, stack: 0 });
+        assert!(matches!(MoveInstruction::parse_line(10, "move one from 1 to 2"), Err(Error::BadInstruction { line: 10, .. })));
+
+        // Emptying a stack leaves a gap rather than shifting the others along.
+        let instruction = MoveInstruction::parse_line(11, "move 1 from 3 to 2").unwrap();
+        pier.move_boxes(&CrateMover9000, &instruction).unwrap();
+        assert_eq!(pier.top_boxes(), "NP ");
+        let instruction = MoveInstruction::parse_line(12, "move 2 from 1 to 3").unwrap();
+        pier.move_boxes(&CrateMover9000, &instruction).unwrap();
+        assert_eq!(pier.top_boxes(), " PZ");
+    }
+
+    #[test]
+    fn test_history() {
+        let mut pier = sample_pier();
+        let start = pier.stacks.clone();
+        for (idx, line) in SAMPLE_MOVES.iter().enumerate() {
+            let instruction = MoveInstruction::parse_line(idx + 1, line).unwrap();
+            pier.move_boxes(&CrateMover9000, &instruction).unwrap();
+        }
+        assert_eq!((pier.step(), pier.num_steps()), (4, 4));
+
+        assert!(pier.undo());
+        assert!(pier.undo());
+        assert_eq!(pier.top_boxes(), " CZ");
+        assert!(pier.redo());
+        assert_eq!(pier.top_boxes(), "M Z");
+        assert!(pier.goto(0));
+        assert_eq!(pier.stacks, start);
+        assert!(!pier.undo());
+        assert!(pier.goto(4));
+        assert_eq!(pier.top_boxes(), "CMZ");
+        assert!(!pier.redo());
+        assert!(!pier.goto(5));
+
+        assert_eq!(pier.diff(0, 1).unwrap(), vec![
+            StackDiff { stack: 1, before: stack("ZN"), after: stack("ZND") },
+            StackDiff { stack: 2, before: stack("MCD"), after: stack("MC") },
+        ]);
+        assert_eq!(pier.diff(2, 2).unwrap(), vec![]);
+
+        // A new move after undoing replaces the undone future.
+        pier.goto(1);
+        let instruction = MoveInstruction::parse_line(9, "move 1 from 3 to 2").unwrap();
+        pier.move_boxes(&CrateMover9000, &instruction).unwrap();
+        assert_eq!((pier.step(), pier.num_steps()), (2, 2));
+        assert_eq!(pier.top_boxes(), "DP ");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let drawing = "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 ";
+        let pier : CranePier = drawing.parse().unwrap();
+        assert_eq!(pier.to_string(), drawing);
+        let reparsed : CranePier = pier.to_string().parse().unwrap();
+        assert_eq!(reparsed.stacks, pier.stacks);
+
+        let wide = "[AB]                                    \n[C]  [D] [E] [F] [G] [H] [I] [J] [K] [L]\n 1    2   3   4   5   6   7   8   9  10 ";
+        let pier : CranePier = wide.parse().unwrap();
+        assert_eq!(pier.top_boxes(), "ABDEFGHIJKL");
+        assert_eq!(pier.to_string(), wide);
+        let reparsed : CranePier = pier.to_string().parse().unwrap();
+        assert_eq!(reparsed.stacks, pier.stacks);
+    }
+}
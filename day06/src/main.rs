@@ -1,20 +1,83 @@
 use std::{io, fs};
-use std::collections::HashSet;
+use std::io::{BufReader, Read};
 
-fn detect_unique_sequence(stream: &str, length: usize) -> i32 {
-    for (i, elts) in stream.as_bytes().windows(length).enumerate() {
-        let set : HashSet<u8> = elts.iter().copied().collect();
-        if set.len() == length {
-            return (i + length).try_into().unwrap();
+/// Sliding window over a byte stream, which knows whether its last `length` bytes are all different.
+///
+/// Keeps a count of each byte value in the window, so every step is O(1) and memory is bounded
+/// by the window length, however long the stream.
+pub struct MarkerDetector {
+    length : usize,
+    window : Vec<u8>, // ring buffer of the last `length` bytes
+    counts : [usize; 256],
+    repeated : usize, // how many byte values appear more than once in the window
+    position : usize // bytes seen so far
+}
+
+impl MarkerDetector {
+    pub fn new(length: usize) -> Self {
+        assert!(length > 0, "A marker needs at least one character.");
+        Self { length, window: vec![0; length], counts: [0; 256], repeated: 0, position: 0 }
+    }
+
+    /// Feed in the next byte. Returns true if the stream so far ends in a marker.
+    pub fn push(&mut self, byte: u8) -> bool {
+        let slot = self.position % self.length;
+        if self.position >= self.length {
+            let old = self.window[slot] as usize;
+            self.counts[old] -= 1;
+            if self.counts[old] == 1 { self.repeated -= 1; }
         }
+        self.window[slot] = byte;
+        self.counts[byte as usize] += 1;
+        if self.counts[byte as usize] == 2 { self.repeated += 1; }
+        self.position += 1;
+
+        self.position >= self.length && self.repeated == 0
+    }
+
+    /// Number of bytes pushed so far.
+    pub fn position(&self) -> usize {
+        self.position
     }
-    return -1;
+}
+
+/// Every marker in a stream, as the number of bytes read when it completes.
+pub struct Markers<R: Read> {
+    bytes : io::Bytes<BufReader<R>>,
+    detector : MarkerDetector
+}
+
+impl<R: Read> Iterator for Markers<R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for byte in self.bytes.by_ref() {
+            match byte {
+                Ok(b) => if self.detector.push(b) { return Some(Ok(self.detector.position())); },
+                Err(e) => return Some(Err(e))
+            }
+        }
+        None
+    }
+}
+
+pub fn markers<R: Read>(reader: R, length: usize) -> Markers<R> {
+    Markers { bytes: BufReader::new(reader).bytes(), detector: MarkerDetector::new(length) }
+}
+
+/// Where the first run of `length` different bytes ends, if there is one.
+pub fn first_marker<R: Read>(reader: R, length: usize) -> io::Result<Option<usize>> {
+    markers(reader, length).next().transpose()
+}
+
+pub fn detect_unique_sequence(stream: &str, length: usize) -> Option<usize> {
+    first_marker(stream.as_bytes(), length).expect("Reading from a &str can't fail.")
 }
 
 fn day06() {
-    let stream = fs::read_to_string("input.txt").unwrap();
-    let part1 = detect_unique_sequence(&stream, 4);
-    let part2 = detect_unique_sequence(&stream, 14);
+    let open = || fs::File::open("input.txt").expect("File 'input.txt' not readable.");
+    let part1 = first_marker(open(), 4).unwrap().expect("No start-of-packet marker.");
+    let part2 = first_marker(open(), 14).unwrap().expect("No start-of-message marker.");
 
     println!("{}, {}", part1, part2);
 }
@@ -29,19 +92,31 @@ fn main() -> io::Result<()> {
 
     #[test]
     fn test_one() {
-        assert_eq!(7, detect_unique_sequence("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 4));
-        assert_eq!(5, detect_unique_sequence("bvwbjplbgvbhsrlpgdmjqwftvncz", 4));
-        assert_eq!(6, detect_unique_sequence("nppdvjthqldpwncqszvftbrmjlhg", 4));
-        assert_eq!(10, detect_unique_sequence("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 4));
-        assert_eq!(11, detect_unique_sequence("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 4));
+        assert_eq!(Some(7), detect_unique_sequence("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 4));
+        assert_eq!(Some(5), detect_unique_sequence("bvwbjplbgvbhsrlpgdmjqwftvncz", 4));
+        assert_eq!(Some(6), detect_unique_sequence("nppdvjthqldpwncqszvftbrmjlhg", 4));
+        assert_eq!(Some(10), detect_unique_sequence("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 4));
+        assert_eq!(Some(11), detect_unique_sequence("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 4));
     }
 
     #[test]
     fn test_two() {
-        assert_eq!(19, detect_unique_sequence("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 14));
-        assert_eq!(23, detect_unique_sequence("bvwbjplbgvbhsrlpgdmjqwftvncz", 14));
-        assert_eq!(23, detect_unique_sequence("nppdvjthqldpwncqszvftbrmjlhg", 14));
-        assert_eq!(29, detect_unique_sequence("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 14));
-        assert_eq!(26, detect_unique_sequence("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 14));
+        assert_eq!(Some(19), detect_unique_sequence("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 14));
+        assert_eq!(Some(23), detect_unique_sequence("bvwbjplbgvbhsrlpgdmjqwftvncz", 14));
+        assert_eq!(Some(23), detect_unique_sequence("nppdvjthqldpwncqszvftbrmjlhg", 14));
+        assert_eq!(Some(29), detect_unique_sequence("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 14));
+        assert_eq!(Some(26), detect_unique_sequence("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 14));
+    }
+
+    #[test]
+    fn test_every_marker() {
+        assert_eq!(None, detect_unique_sequence("aaaaaa", 2));
+        assert_eq!(None, detect_unique_sequence("abc", 4));
+        assert_eq!(Some(1), detect_unique_sequence("abc", 1));
+
+        let all : Vec<usize> = markers("aabcbdd".as_bytes(), 3).map(|m| m.unwrap()).collect();
+        assert_eq!(all, vec![4, 6]);
+        let all : Vec<usize> = markers("abcabc".as_bytes(), 3).map(|m| m.unwrap()).collect();
+        assert_eq!(all, vec![3, 4, 5, 6]);
     }
-}
\ No newline at end of file
+}
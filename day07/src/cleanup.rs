@@ -0,0 +1,366 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::vfs::{NodeId, VirtualFs};
+
+/// What the planner is allowed to delete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// The single smallest directory that frees enough, as in part 2.
+    SmallestDirectory,
+    /// The smallest total of directories, none inside another.
+    Directories,
+    /// The smallest total of individual files.
+    Files,
+    /// The smallest total of files and directories, preferring whole directories where possible.
+    Any
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanError {
+    /// A path to keep doesn't exist.
+    NoSuchPath(String),
+    /// More is already used than the disk can hold.
+    OverCapacity { used: usize, capacity: usize },
+    /// Even deleting everything we're allowed to wouldn't free enough.
+    Impossible { needed: usize }
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlanError::NoSuchPath(path) => write!(f, "can't keep {}, it doesn't exist", path),
+            PlanError::OverCapacity { used, capacity } =>
+                write!(f, "{} used on a disk of only {}", used, capacity),
+            PlanError::Impossible { needed } =>
+                write!(f, "there's no way to free {} without deleting something we have to keep", needed),
+        }
+    }
+}
+
+impl std::error::Error for PlanError {}
+
+/// What to delete, and where that leaves the disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub delete : Vec<NodeId>,
+    pub freed : usize,
+    pub free_before : usize,
+    pub free_after : usize
+}
+
+impl Plan {
+    /// One line per deletion, then the totals.
+    pub fn describe(&self, fs: &VirtualFs) -> String {
+        let mut lines : Vec<String> = self.delete.iter()
+            .map(|&id| format!("rm {}{}\t{}", if fs.is_dir(id) { "-r " } else { "" }, fs.path(id), fs.size(id)))
+            .collect();
+        lines.push(format!("Frees {}, taking free space from {} to {}.", self.freed, self.free_before, self.free_after));
+        lines.join("\n")
+    }
+}
+
+/// Chooses what to delete to get a disk down to a target amount of free space.
+#[derive(Debug, Clone)]
+pub struct CleanupPlanner {
+    pub capacity : usize,
+    pub target_free : usize,
+    /// Paths that must survive, along with everything in them.
+    pub keep : Vec<String>
+}
+
+impl Default for CleanupPlanner {
+    fn default() -> Self {
+        Self::new(70000000, 30000000)
+    }
+}
+
+impl CleanupPlanner {
+    pub fn new(capacity: usize, target_free: usize) -> Self {
+        Self { capacity, target_free, keep: Vec::new() }
+    }
+
+    pub fn keep(mut self, path: &str) -> Self {
+        self.keep.push(path.to_string());
+        self
+    }
+
+    /// How much has to go, or zero if there's already enough space.
+    pub fn needed(&self, fs: &VirtualFs) -> Result<usize, PlanError> {
+        let used = fs.size(fs.root());
+        let free = self.capacity.checked_sub(used)
+            .ok_or(PlanError::OverCapacity { used, capacity: self.capacity })?;
+        Ok(self.target_free.saturating_sub(free))
+    }
+
+    /// Nodes we can't delete: the root, anything kept, anything in something kept, and anything
+    /// holding something kept.
+    fn protected(&self, fs: &VirtualFs) -> Result<HashSet<NodeId>, PlanError> {
+        let mut protected = HashSet::from([fs.root()]);
+        for path in self.keep.iter() {
+            let kept = fs.lookup(path).ok_or_else(|| PlanError::NoSuchPath(path.clone()))?;
+            protected.extend(fs.pre_order(kept));
+            protected.extend(std::iter::successors(Some(kept), |&id| fs.parent(id)));
+        }
+        Ok(protected)
+    }
+
+    pub fn plan(&self, fs: &VirtualFs, strategy: Strategy) -> Result<Plan, PlanError> {
+        let needed = self.needed(fs)?;
+        let free_before = self.capacity - fs.size(fs.root());
+        let delete = if needed == 0 {
+            Vec::new()
+        } else {
+            let protected = self.protected(fs)?;
+            let deletable = |&id: &NodeId| !protected.contains(&id);
+            let chosen = match strategy {
+                Strategy::SmallestDirectory => fs.directories()
+                    .filter(deletable)
+                    .filter(|&id| fs.size(id) >= needed)
+                    .min_by_key(|&id| fs.size(id))
+                    .map(|id| vec![id]),
+                Strategy::Directories => {
+                    let dirs : Vec<NodeId> = fs.directories().filter(deletable).collect();
+                    smallest_antichain(fs, &dirs, needed)
+                },
+                Strategy::Files | Strategy::Any => {
+                    let files : Vec<NodeId> = fs.pre_order(fs.root())
+                        .filter(|&id| !fs.is_dir(id))
+                        .filter(deletable)
+                        .collect();
+                    let chosen = smallest_antichain(fs, &files, needed);
+                    match strategy {
+                        Strategy::Any => chosen.map(|files| merge_into_directories(fs, &files, &deletable)),
+                        _ => chosen
+                    }
+                }
+            };
+            chosen.ok_or(PlanError::Impossible { needed })?
+        };
+
+        let freed = delete.iter().map(|&id| fs.size(id)).sum();
+        Ok(Plan { delete, freed, free_before, free_after: free_before + freed })
+    }
+}
+
+/// A set of bits `0..len`, for the subset-sum search.
+#[derive(Debug, Clone)]
+struct BitSet(Vec<u64>);
+
+impl BitSet {
+    fn new(len: usize) -> Self {
+        BitSet(vec![0; len.div_ceil(64)])
+    }
+
+    fn get(&self, bit: usize) -> bool {
+        self.0.get(bit / 64).is_some_and(|word| word >> (bit % 64) & 1 == 1)
+    }
+
+    fn set(&mut self, bit: usize) {
+        if let Some(word) = self.0.get_mut(bit / 64) { *word |= 1 << (bit % 64); }
+    }
+
+    /// `self |= other << shift`, dropping anything shifted off the end.
+    fn or_shifted(&mut self, other: &BitSet, shift: usize) {
+        let (words, bits) = (shift / 64, shift % 64);
+        for idx in (words..self.0.len()).rev() {
+            let mut shifted = other.0[idx - words] << bits;
+            if bits > 0 && idx > words {
+                shifted |= other.0[idx - words - 1] >> (64 - bits);
+            }
+            self.0[idx] |= shifted;
+        }
+    }
+
+    /// `self |= other`, calling `newly_set` with each bit that wasn't already set.
+    fn or_reporting(&mut self, other: &BitSet, mut newly_set: impl FnMut(usize)) {
+        for (idx, (word, &incoming)) in self.0.iter_mut().zip(&other.0).enumerate() {
+            let mut new = incoming & !*word;
+            while new != 0 {
+                newly_set(idx * 64 + new.trailing_zeros() as usize);
+                new &= new - 1;
+            }
+            *word |= incoming;
+        }
+    }
+
+    fn first_at_or_after(&self, start: usize) -> Option<usize> {
+        (start..self.0.len() * 64).find(|&bit| self.get(bit))
+    }
+}
+
+/// The smallest total size, at least `needed`, of candidates with none inside another.
+///
+/// `candidates` must be in pre-order, so each one's contents directly follow it. A set that
+/// can't be improved on has a total under `needed` plus its largest member, so anything too
+/// big to share is only ever considered on its own, and the rest is an exact subset-sum search
+/// over totals below that bound.
+///
+/// The search walks the candidates in order. `reachable` holds every total that can be made
+/// from the candidates before the current one; taking a candidate makes new totals that only
+/// become usable once the walk is past its contents, so they wait in `pending` until then.
+/// Only candidates the walk is still inside have anything pending, so there are never more
+/// bitsets than the tree is deep. `taken_by` remembers which candidate first made each total,
+/// which is enough to work back to a set of candidates that makes it.
+fn smallest_antichain(fs: &VirtualFs, candidates: &[NodeId], needed: usize) -> Option<Vec<NodeId>> {
+    let best_single = candidates.iter().copied()
+        .filter(|&id| fs.size(id) >= needed)
+        .min_by_key(|&id| fs.size(id));
+
+    let small = |id: NodeId| fs.size(id) > 0 && fs.size(id) < needed;
+    let limit = needed + candidates.iter().copied().filter(|&id| small(id)).map(|id| fs.size(id)).max().unwrap_or(0);
+
+    // Where to carry on from after taking each candidate: the first one not inside it.
+    let inside = |outer: NodeId, inner: NodeId| {
+        std::iter::successors(fs.parent(inner), |&id| fs.parent(id)).any(|id| id == outer)
+    };
+    let skip_to : Vec<usize> = (0..candidates.len())
+        .map(|i| (i + 1..candidates.len()).find(|&j| !inside(candidates[i], candidates[j])).unwrap_or(candidates.len()))
+        .collect();
+
+    let mut reachable = BitSet::new(limit);
+    reachable.set(0);
+    let mut taken_by = vec![u32::MAX; reachable.0.len() * 64];
+    let mut pending : Vec<(usize, usize, BitSet)> = Vec::new();
+    for i in 0..=candidates.len() {
+        let (arriving, waiting) = pending.into_iter().partition(|&(at, _, _)| at == i);
+        pending = waiting;
+        for (_, taken, totals) in arriving {
+            reachable.or_reporting(&totals, |total| taken_by[total] = taken as u32);
+        }
+        if i < candidates.len() && small(candidates[i]) {
+            let mut totals = BitSet::new(limit);
+            totals.or_shifted(&reachable, fs.size(candidates[i]));
+            pending.push((skip_to[i], i, totals));
+        }
+    }
+
+    let best_total = reachable.first_at_or_after(needed);
+    match (best_total, best_single) {
+        (Some(total), single) if single.is_none_or(|id| total < fs.size(id)) => {
+            // Each total was first made from one that was already there before its candidate.
+            let mut chosen = Vec::new();
+            let mut remaining = total;
+            while remaining > 0 {
+                let taken = candidates[taken_by[remaining] as usize];
+                chosen.push(taken);
+                remaining -= fs.size(taken);
+            }
+            chosen.reverse();
+            Some(chosen)
+        },
+        (_, single) => single.map(|id| vec![id])
+    }
+}
+
+/// Replace files with the biggest deletable directories that only hold chosen files.
+fn merge_into_directories(fs: &VirtualFs, files: &[NodeId], deletable: &dyn Fn(&NodeId) -> bool) -> Vec<NodeId> {
+    let chosen : HashSet<NodeId> = files.iter().copied().collect();
+    let all_chosen = |dir: NodeId| {
+        let mut files = fs.pre_order(dir).filter(|&id| !fs.is_dir(id)).peekable();
+        files.peek().is_some() && files.all(|id| chosen.contains(&id))
+    };
+
+    let mut merged = Vec::new();
+    let mut covered : Option<NodeId> = None;
+    for id in fs.pre_order(fs.root()) {
+        if covered.is_some_and(|dir| std::iter::successors(fs.parent(id), |&p| fs.parent(p)).any(|p| p == dir)) {
+            continue;
+        }
+        if fs.is_dir(id) && deletable(&id) && all_chosen(id) {
+            merged.push(id);
+            covered = Some(id);
+        } else if chosen.contains(&id) {
+            merged.push(id);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+    use crate::shell::{interpret, SAMPLE};
+
+    fn paths(fs: &VirtualFs, plan: &Plan) -> Vec<String> {
+        plan.delete.iter().map(|&id| fs.path(id)).collect()
+    }
+
+    #[test]
+    fn test_smallest_directory() {
+        let (fs, _) = interpret(SAMPLE.lines());
+        let planner = CleanupPlanner::default();
+        assert_eq!(planner.needed(&fs), Ok(8381165));
+        let plan = planner.plan(&fs, Strategy::SmallestDirectory).unwrap();
+        assert_eq!(paths(&fs, &plan), vec!["/d"]);
+        assert_eq!((plan.freed, plan.free_before, plan.free_after), (24933642, 21618835, 46552477));
+
+        let plan = planner.clone().keep("/d/k").plan(&fs, Strategy::SmallestDirectory);
+        assert_eq!(plan, Err(PlanError::Impossible { needed: 8381165 }));
+        assert_eq!(planner.clone().keep("/x").plan(&fs, Strategy::Files), Err(PlanError::NoSuchPath("/x".to_string())));
+    }
+
+    #[test]
+    fn test_minimal_sets() {
+        let (fs, _) = interpret(SAMPLE.lines());
+        let planner = CleanupPlanner::default();
+
+        let plan = planner.plan(&fs, Strategy::Files).unwrap();
+        assert_eq!(plan.freed, 8381165 + brute_force_excess(&fs, 8381165));
+        assert_eq!(plan.free_after, 30000000 + brute_force_excess(&fs, 8381165));
+
+        let plan = planner.plan(&fs, Strategy::Directories).unwrap();
+        assert_eq!(paths(&fs, &plan), vec!["/d"]);
+
+        // Plenty of room already.
+        let plan = CleanupPlanner::new(100000000, 10).plan(&fs, Strategy::Any).unwrap();
+        assert_eq!(plan.delete, vec![]);
+    }
+
+    #[test]
+    fn test_any_prefers_directories() {
+        let (fs, _) = interpret(SAMPLE.lines());
+        // Need exactly /a: 94853 is only reachable by taking all of /a's files.
+        let planner = CleanupPlanner::new(48381165 + 10, 94853 + 10);
+        assert_eq!(paths(&fs, &planner.plan(&fs, Strategy::Any).unwrap()), vec!["/a"]);
+        assert_eq!(paths(&fs, &planner.plan(&fs, Strategy::Files).unwrap()), vec!["/a/e/i", "/a/f", "/a/g", "/a/h.lst"]);
+        assert_eq!(paths(&fs, &planner.plan(&fs, Strategy::Directories).unwrap()), vec!["/a"]);
+
+        // Keeping /a/e means /a itself has to stay, but its other files can go.
+        let plan = planner.keep("/a/e").plan(&fs, Strategy::Any).unwrap();
+        assert!(!plan.delete.contains(&fs.lookup("/a").unwrap()));
+        assert!(!plan.delete.contains(&fs.lookup("/a/e/i").unwrap()));
+    }
+
+    #[test]
+    fn test_nested_directories() {
+        let (fs, _) = interpret(SAMPLE.lines());
+        let dirs : Vec<NodeId> = fs.directories().filter(|&id| id != fs.root()).collect();
+        let inside = |outer: NodeId, inner: NodeId| std::iter::successors(fs.parent(inner), |&id| fs.parent(id)).any(|id| id == outer);
+        for needed in [1, 584, 585, 94000, 94853, 95000, 24933642, 24933643, 25028495, 25028496] {
+            let best = (0..1usize << dirs.len())
+                .map(|mask| dirs.iter().enumerate().filter(|(i, _)| mask >> i & 1 == 1).map(|(_, &id)| id).collect::<Vec<_>>())
+                .filter(|set| set.iter().all(|&a| set.iter().all(|&b| !inside(a, b))))
+                .map(|set| set.iter().map(|&id| fs.size(id)).sum::<usize>())
+                .filter(|&total| total >= needed)
+                .min();
+
+            // No free space at all, so everything the target asks for has to be freed.
+            let plan = CleanupPlanner::new(48381165, needed).plan(&fs, Strategy::Directories);
+            assert_eq!(plan.as_ref().ok().map(|plan| plan.freed), best, "needing {}", needed);
+            if let Ok(plan) = plan {
+                assert!(plan.delete.iter().all(|&a| plan.delete.iter().all(|&b| !inside(a, b))));
+            }
+        }
+    }
+
+    /// Smallest excess over `needed` from any set of files, by trying them all.
+    fn brute_force_excess(fs: &VirtualFs, needed: usize) -> usize {
+        let sizes : Vec<usize> = fs.pre_order(fs.root()).filter(|&id| !fs.is_dir(id)).map(|id| fs.size(id)).collect();
+        (0..1usize << sizes.len())
+            .map(|mask| (0..sizes.len()).filter(|i| mask >> i & 1 == 1).map(|i| sizes[i]).sum::<usize>())
+            .filter(|&total| total >= needed)
+            .min()
+            .unwrap() - needed
+    }
+}
@@ -0,0 +1,139 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::vfs::{NodeId, VirtualFs};
+
+/// Path of a node relative to the root, like `a/b/c`, for building real paths.
+fn relative_path(fs: &VirtualFs, id: NodeId) -> PathBuf {
+    PathBuf::from(fs.path(id).trim_start_matches('/'))
+}
+
+/// Recreate the tree under `root` as real directories and files.
+///
+/// Files are the listed size but full of nothing: `set_len` leaves them sparse on any
+/// filesystem that supports it, so this doesn't actually need 40MB of disk.
+pub fn materialise(fs: &VirtualFs, root: &Path) -> io::Result<()> {
+    fs::create_dir_all(root)?;
+    for id in fs.pre_order(fs.root()).skip(1) {
+        let path = root.join(relative_path(fs, id));
+        if fs.is_dir(id) {
+            fs::create_dir_all(&path)?;
+        } else {
+            File::create(&path)?.set_len(fs.size(id) as u64)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the tree as a tar stream, with zero-filled files of the listed sizes.
+pub fn write_tar<W: Write>(fs: &VirtualFs, writer: W) -> io::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    for id in fs.pre_order(fs.root()).skip(1) {
+        let mut header = tar::Header::new_gnu();
+        if fs.is_dir(id) {
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_mode(0o755);
+            header.set_size(0);
+            header.set_cksum();
+            builder.append_data(&mut header, relative_path(fs, id), io::empty())?;
+        } else {
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_mode(0o644);
+            header.set_size(fs.size(id) as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, relative_path(fs, id), io::repeat(0).take(fs.size(id) as u64))?;
+        }
+    }
+    builder.into_inner()
+}
+
+/// Read a real directory into a `VirtualFs`. Symlinks and other special files are skipped.
+pub fn scan(root: &Path) -> io::Result<VirtualFs> {
+    let mut vfs = VirtualFs::new();
+    let mut pending = vec![(root.to_path_buf(), vfs.root())];
+    while let Some((path, dir)) = pending.pop() {
+        for entry in fs::read_dir(&path)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let file_type = entry.file_type()?;
+            let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+            if file_type.is_dir() {
+                let id = vfs.mkdir(dir, &name).map_err(invalid)?;
+                pending.push((entry.path(), id));
+            } else if file_type.is_file() {
+                vfs.add_file(dir, &name, entry.metadata()?.len() as usize).map_err(invalid)?;
+            }
+        }
+    }
+    Ok(vfs)
+}
+
+/// A puzzle-style terminal transcript that explores the whole tree with `cd` and `ls`.
+pub fn transcript(fs: &VirtualFs) -> String {
+    let mut lines = vec!["$ cd /".to_string()];
+    explore(fs, fs.root(), &mut lines);
+    lines.join("\n") + "\n"
+}
+
+fn explore(fs: &VirtualFs, dir: NodeId, lines: &mut Vec<String>) {
+    lines.push("$ ls".to_string());
+    for child in fs.children(dir) {
+        let node = fs.node(child);
+        if node.is_dir() {
+            lines.push(format!("dir {}", node.name()));
+        } else {
+            lines.push(format!("{} {}", node.size(), node.name()));
+        }
+    }
+    for child in fs.children(dir).filter(|&child| fs.is_dir(child)) {
+        lines.push(format!("$ cd {}", fs.node(child).name()));
+        explore(fs, child, lines);
+        lines.push("$ cd ..".to_string());
+    }
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+    use crate::query::tree;
+    use crate::shell::{interpret, SAMPLE};
+
+    #[test]
+    fn test_round_trip() {
+        let (original, _) = interpret(SAMPLE.lines());
+        let mut with_empty = original.clone();
+        let d = with_empty.lookup("/d").unwrap();
+        with_empty.mkdir(d, "empty").unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        materialise(&with_empty, dir.path()).unwrap();
+        assert_eq!(fs::metadata(dir.path().join("d/k")).unwrap().len(), 7214296);
+        assert!(dir.path().join("d/empty").is_dir());
+
+        let scanned = scan(dir.path()).unwrap();
+        assert_eq!(tree(&scanned, scanned.root()), tree(&with_empty, with_empty.root()));
+
+        let text = transcript(&scanned);
+        let (reparsed, warnings) = interpret(text.lines());
+        assert_eq!(warnings, vec![]);
+        assert_eq!(tree(&reparsed, reparsed.root()), tree(&with_empty, with_empty.root()));
+        assert!(text.starts_with("$ cd /\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\ndir d\n$ cd a\n$ ls\ndir e\n"));
+    }
+
+    #[test]
+    fn test_tar() {
+        let (fs, _) = interpret(SAMPLE.lines());
+        let bytes = write_tar(&fs, Vec::new()).unwrap();
+        let mut archive = tar::Archive::new(bytes.as_slice());
+        let entries : Vec<(String, u64)> = archive.entries().unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                (entry.path().unwrap().to_string_lossy().into_owned(), entry.header().size().unwrap())
+            })
+            .collect();
+        assert_eq!(entries.len(), fs.len() - 1);
+        assert_eq!(entries[0], ("a".to_string(), 0));
+        assert!(entries.contains(&("d/d.log".to_string(), 8033020)));
+    }
+}
@@ -1,107 +1,224 @@
+use std::cell::LazyCell;
+use std::env;
 use std::fs::File;
-use std::collections::HashMap;
-use std::collections::hash_map::Entry;
+use std::path::PathBuf;
+use std::process;
 use std::io::{self, prelude::*, BufReader};
 
-#[derive(Default, Debug)]
-struct DirectoryListing {
-    dirs: HashMap<String, Box<DirectoryListing>>, 
-    files : HashMap<String, usize>
-}
+use glob::Pattern;
 
-impl DirectoryListing {
-    fn new() -> Self {
-        return DirectoryListing {dirs: HashMap::new(), files: HashMap::new() }
-    }
+pub mod cleanup;
+pub mod export;
+pub mod query;
+pub mod shell;
+pub mod vfs;
 
-    fn size(&self) -> usize {
-        let mut total = 0;
-        for (_, size) in self.files.iter() { total += size; }
-        for (_, dir) in self.dirs.iter() { total += dir.size(); }
-        total
-    }
+use cleanup::{CleanupPlanner, Strategy};
+use query::{FindQuery, Kind};
+use vfs::{NodeId, VirtualFs};
 
-    fn set_file_size(&mut self, path: &mut std::slice::Iter<String>, name: &str, size: usize) {
-        // We recurse through subdirectories, until we empty out path, then set the file size
-        match path.next() {
-            // No more subdirectories, so ensure this child file listing exists
-            None => {
-                self.files.entry(name.to_string()).or_insert(size);
-            },
-
-            // Follow subdirectory listing one level deeper, and recurse
-            Some(subdir) => match self.dirs.entry(subdir.to_string()) {
-                Entry::Occupied(mut entry) => {
-                    entry.get_mut().set_file_size(path, name, size);
-                },
-
-                Entry::Vacant(entry) => {
-                    let listing = entry.insert(Box::new(DirectoryListing::new()));
-                    listing.set_file_size(path, name, size);
-                }
-            }
-        }
-    }
+/// Rebuild the filesystem from the transcript in input.txt, complaining about anything odd.
+fn load() -> VirtualFs {
+    let file = File::open("input.txt").expect("File 'input.txt' not readable.");
+    let reader = BufReader::new(file)
+        .lines() // Get a line iterator
+        .map_while(Result::ok); // Get Strings instead of Result
 
-    fn collect_subdirectory_sizes(&self, sizes: &mut Vec<usize>) {
-        for dir in self.dirs.values() {
-            sizes.push(dir.size());
-            dir.collect_subdirectory_sizes(sizes);
-        }
+    let (fs, warnings) = shell::interpret(reader);
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
     }
+    fs
 }
 
 fn day07() {
-    let file = File::open("input.txt").expect("File 'input.txt' not readable.");
-    let reader = BufReader::new(file)
-        .lines() // Get a line iterator
-        .filter_map(|line| line.ok().to_owned()); // Get Strings instead of Result
-
-    let mut root = Box::new(DirectoryListing::new());
-    let mut path : Vec<String> = Vec::new();
-
-    for line in reader {
-        if line.starts_with("$") {
-            let args : Vec<&str> = line[2..].split(" ").collect();
-            match args[0] {
-                "cd" => match args[1] {
-                    "/" => { path.clear(); },
-                    ".." => { path.pop(); },
-                    dir => { path.push(dir.to_string()); }
-                },
-                "ls" => { }, // We assume any unrecognized output is from 'ls'
-                _ => unreachable!()
+    let fs = load();
+
+    let total_under_100k: usize = fs.directories()
+        .filter(|&id| id != fs.root())
+        .map(|id| fs.size(id))
+        .filter(|x| *x <= 100000)
+        .sum();
+    println!("Total size of files at or under 100kB is {:?}", total_under_100k);
+
+    let plan = CleanupPlanner::default().plan(&fs, Strategy::SmallestDirectory).unwrap();
+    println!("Smallest directory to free up enough space is {:?}", plan.freed);
+}
+
+const USAGE: &str = "\
+usage: day07                  solve the puzzle
+       day07 tree [PATH]
+       day07 du [-d DEPTH] [PATH]
+       day07 find [PATH] [-name GLOB] [-size [+|-]N]... [-type f|d]
+       day07 largest N [files|dirs]
+       day07 clean [--capacity N] [--target N] [--keep PATH]... [--strategy smallest|dirs|files|any]
+       day07 export DIR          recreate the tree under DIR with sparse files
+       day07 tar FILE            write the tree as a tar archive, or to stdout for '-'
+       day07 scan DIR            print a transcript exploring a real directory";
+
+/// A query to run against the rebuilt filesystem, from the command line.
+#[derive(Debug)]
+enum Command {
+    Tree(String),
+    Du(String, Option<usize>),
+    Find(String, FindQuery),
+    Largest(usize, Kind),
+    Clean(CleanupPlanner, Strategy),
+    Export(PathBuf),
+    Tar(String),
+    Scan(PathBuf)
+}
+
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let value = |idx: usize| args.get(idx).ok_or(format!("{} needs a value", args[idx - 1]));
+    let bad = |what: &str, value: &str| format!("bad {} '{}'", what, value);
+    // Commands take at most one bare path; it defaults to the root.
+    let mut path : Option<String> = None;
+    let mut set_path = |arg: &str| match path.replace(arg.to_string()) {
+        Some(_) => Err(format!("unexpected argument '{}'", arg)),
+        None => Ok(())
+    };
+
+    match args.first().map(|s| s.as_str()) {
+        Some("tree") => {
+            for arg in &args[1..] { set_path(arg)?; }
+            Ok(Command::Tree(path.unwrap_or_else(|| "/".to_string())))
+        },
+        Some("du") => {
+            let mut depth = None;
+            let mut idx = 1;
+            while idx < args.len() {
+                match args[idx].as_str() {
+                    "-d" => {
+                        idx += 1;
+                        depth = Some(value(idx)?.parse().map_err(|_| bad("depth", &args[idx]))?);
+                    },
+                    arg => set_path(arg)?
+                }
+                idx += 1;
             }
-        } else {
-            let mut parts = line.split(" ");
-            let size_or_dir = parts.next().unwrap();
-            let name = parts.next().unwrap();
-            match size_or_dir {
-                // Ignore directory listings.  They have no size.
-                "dir" => { },
-                // Numerical file sizes - we care about these.  Store them.
-                size_str => {
-                    let size : usize = size_str.parse().unwrap();
-                    root.set_file_size(&mut path.iter(), name, size);
+            Ok(Command::Du(path.unwrap_or_else(|| "/".to_string()), depth))
+        },
+        Some("find") => {
+            let mut query = FindQuery::default();
+            let mut idx = 1;
+            while idx < args.len() {
+                match args[idx].as_str() {
+                    "-name" => {
+                        idx += 1;
+                        query.name = Some(Pattern::new(value(idx)?).map_err(|_| bad("glob", &args[idx]))?);
+                    },
+                    "-size" => {
+                        idx += 1;
+                        query.sizes.push(value(idx)?.parse().map_err(|_| bad("size", &args[idx]))?);
+                    },
+                    "-type" => {
+                        idx += 1;
+                        query.kind = Some(match value(idx)?.as_str() {
+                            "f" => Kind::File,
+                            "d" => Kind::Directory,
+                            other => return Err(bad("type", other))
+                        });
+                    },
+                    arg => set_path(arg)?
                 }
+                idx += 1;
             }
-        }
+            Ok(Command::Find(path.unwrap_or_else(|| "/".to_string()), query))
+        },
+        Some("largest") => {
+            let n = value(1)?.parse().map_err(|_| bad("count", &args[1]))?;
+            let kind = match args.get(2).map(|s| s.as_str()) {
+                None | Some("files") => Kind::File,
+                Some("dirs") => Kind::Directory,
+                Some(other) => return Err(bad("kind", other))
+            };
+            Ok(Command::Largest(n, kind))
+        },
+        Some("clean") => {
+            let mut planner = CleanupPlanner::default();
+            let mut strategy = Strategy::SmallestDirectory;
+            let mut idx = 1;
+            while idx < args.len() {
+                let flag = args[idx].as_str();
+                idx += 1;
+                let arg = value(idx)?;
+                match flag {
+                    "--capacity" => { planner.capacity = arg.parse().map_err(|_| bad("capacity", arg))?; },
+                    "--target" => { planner.target_free = arg.parse().map_err(|_| bad("target", arg))?; },
+                    "--keep" => { planner = planner.keep(arg); },
+                    "--strategy" => {
+                        strategy = match arg.as_str() {
+                            "smallest" => Strategy::SmallestDirectory,
+                            "dirs" => Strategy::Directories,
+                            "files" => Strategy::Files,
+                            "any" => Strategy::Any,
+                            other => return Err(bad("strategy", other))
+                        };
+                    },
+                    other => return Err(format!("unexpected argument '{}'", other))
+                }
+                idx += 1;
+            }
+            Ok(Command::Clean(planner, strategy))
+        },
+        Some("export") if args.len() == 2 => Ok(Command::Export(PathBuf::from(&args[1]))),
+        Some("tar") if args.len() == 2 => Ok(Command::Tar(args[1].clone())),
+        Some("scan") if args.len() == 2 => Ok(Command::Scan(PathBuf::from(&args[1]))),
+        Some(command @ ("export" | "tar" | "scan")) => Err(format!("{} takes exactly one path", command)),
+        Some(other) => Err(format!("unknown command '{}'", other)),
+        None => Err("no command".to_string())
     }
+}
 
-    let mut all_subdirectory_sizes = Vec::new();
-    root.collect_subdirectory_sizes(&mut all_subdirectory_sizes);
-    all_subdirectory_sizes.sort();
-    let total_under_100k: usize = all_subdirectory_sizes.iter().filter(|x| **x <= 100000).sum();
-    println!("Total size of files at or under 100kB is {:?}", total_under_100k);
-
-    let current_free_space = 70000000 - root.size();
-    let need_to_free = 30000000 - current_free_space;
+fn run(command: Command) -> Result<(), String> {
+    // Only read the puzzle input once something needs it: scanning a real directory doesn't.
+    let fs = LazyCell::new(load);
+    let lookup = |path: &str| fs.lookup(path).ok_or(format!("no such file or directory: {}", path));
+    let print_sizes = |ids: &[NodeId]| {
+        for &id in ids { println!("{}\t{}", fs.size(id), fs.path(id)); }
+    };
 
-    let smallest_possible = all_subdirectory_sizes.iter().filter(|x| **x >= need_to_free).next();
-    println!("Smallest directory to free up enough space is {:?}", smallest_possible.unwrap());
+    match command {
+        Command::Tree(path) => print!("{}", query::tree(&fs, lookup(&path)?)),
+        Command::Du(path, depth) => {
+            let dirs : Vec<NodeId> = query::du(&fs, lookup(&path)?, depth).into_iter().map(|(id, _)| id).collect();
+            print_sizes(&dirs);
+        },
+        Command::Find(path, query) => {
+            for id in query::find(&fs, lookup(&path)?, &query) { println!("{}", fs.path(id)); }
+        },
+        Command::Largest(n, kind) => print_sizes(&query::largest(&fs, n, kind)),
+        Command::Clean(planner, strategy) => {
+            let plan = planner.plan(&fs, strategy).map_err(|e| e.to_string())?;
+            println!("{}", plan.describe(&fs));
+        },
+        Command::Export(dir) => export::materialise(&fs, &dir).map_err(|e| format!("{}: {}", dir.display(), e))?,
+        Command::Tar(path) => {
+            let result = match path.as_str() {
+                "-" => export::write_tar(&fs, io::stdout().lock()).map(|_| ()),
+                _ => File::create(&path).and_then(|file| export::write_tar(&fs, file)).map(|_| ())
+            };
+            result.map_err(|e| format!("{}: {}", path, e))?;
+        },
+        Command::Scan(dir) => {
+            let scanned = export::scan(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+            print!("{}", export::transcript(&scanned));
+        }
+    }
+    Ok(())
 }
 
 fn main() -> io::Result<()> {
-    day07();
+    let args : Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() {
+        day07();
+        return Ok(());
+    }
+
+    if let Err(e) = parse_args(&args).and_then(run) {
+        eprintln!("day07: {}\n{}", e, USAGE);
+        process::exit(2);
+    }
     Ok(())
 }
@@ -0,0 +1,163 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use glob::Pattern;
+
+use crate::vfs::{NodeId, VirtualFs};
+
+/// Draw the tree under `start` like the puzzle statement does, with sizes for everything.
+pub fn tree(fs: &VirtualFs, start: NodeId) -> String {
+    let mut out = String::new();
+    let base_depth = depth(fs, start);
+    for id in fs.pre_order(start) {
+        let node = fs.node(id);
+        let name = if node.parent().is_none() { "/" } else { node.name() };
+        let kind = if node.is_dir() { "dir" } else { "file" };
+        let indent = "  ".repeat(depth(fs, id) - base_depth);
+        writeln!(out, "{}- {} ({}, size={})", indent, name, kind, node.size()).unwrap();
+    }
+    out
+}
+
+fn depth(fs: &VirtualFs, id: NodeId) -> usize {
+    std::iter::successors(fs.parent(id), |&p| fs.parent(p)).count()
+}
+
+/// Like `du -d N`: every directory under `start` at most `max_depth` levels down, with its
+/// total size. Contents come before the directory that holds them.
+pub fn du(fs: &VirtualFs, start: NodeId, max_depth: Option<usize>) -> Vec<(NodeId, usize)> {
+    let base_depth = depth(fs, start);
+    fs.post_order(start)
+        .filter(|&id| fs.is_dir(id))
+        .filter(|&id| max_depth.is_none_or(|max| depth(fs, id) - base_depth <= max))
+        .map(|id| (id, fs.size(id)))
+        .collect()
+}
+
+/// A `find -size` style test: `+N` is more than N, `-N` less than N, and `N` exactly N.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeFilter {
+    Greater(usize),
+    Less(usize),
+    Exactly(usize)
+}
+
+impl SizeFilter {
+    pub fn matches(&self, size: usize) -> bool {
+        match *self {
+            SizeFilter::Greater(n) => size > n,
+            SizeFilter::Less(n) => size < n,
+            SizeFilter::Exactly(n) => size == n
+        }
+    }
+}
+
+impl FromStr for SizeFilter {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.as_bytes().first() {
+            Some(b'+') => SizeFilter::Greater(s[1..].parse()?),
+            Some(b'-') => SizeFilter::Less(s[1..].parse()?),
+            _ => SizeFilter::Exactly(s.parse()?)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Directory
+}
+
+/// Tests for `find`. Every test that's set has to pass.
+#[derive(Debug, Clone, Default)]
+pub struct FindQuery {
+    /// Glob on the node's own name, like `find -name`.
+    pub name : Option<Pattern>,
+    pub sizes : Vec<SizeFilter>,
+    pub kind : Option<Kind>
+}
+
+impl FindQuery {
+    pub fn matches(&self, fs: &VirtualFs, id: NodeId) -> bool {
+        let node = fs.node(id);
+        self.name.as_ref().is_none_or(|pattern| pattern.matches(node.name()))
+            && self.sizes.iter().all(|filter| filter.matches(node.size()))
+            && self.kind.is_none_or(|kind| (kind == Kind::Directory) == node.is_dir())
+    }
+}
+
+/// Everything under `start` (inclusive) that matches the query, in pre-order.
+pub fn find(fs: &VirtualFs, start: NodeId, query: &FindQuery) -> Vec<NodeId> {
+    fs.pre_order(start).filter(|&id| query.matches(fs, id)).collect()
+}
+
+/// The `n` biggest files or directories, biggest first. Ties are broken by path.
+pub fn largest(fs: &VirtualFs, n: usize, kind: Kind) -> Vec<NodeId> {
+    let mut nodes : Vec<NodeId> = fs.pre_order(fs.root())
+        .filter(|&id| (kind == Kind::Directory) == fs.is_dir(id))
+        .collect();
+    nodes.sort_by_cached_key(|&id| (std::cmp::Reverse(fs.size(id)), fs.path(id)));
+    nodes.truncate(n);
+    nodes
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+    use crate::shell::{interpret, SAMPLE};
+
+    fn paths(fs: &VirtualFs, ids: &[NodeId]) -> Vec<String> {
+        ids.iter().map(|&id| fs.path(id)).collect()
+    }
+
+    #[test]
+    fn test_tree() {
+        let (fs, _) = interpret(SAMPLE.lines());
+        assert_eq!(tree(&fs, fs.lookup("/a").unwrap()), "\
+- a (dir, size=94853)
+  - e (dir, size=584)
+    - i (file, size=584)
+  - f (file, size=29116)
+  - g (file, size=2557)
+  - h.lst (file, size=62596)
+");
+        assert!(tree(&fs, fs.root()).starts_with("- / (dir, size=48381165)\n  - a (dir"));
+    }
+
+    #[test]
+    fn test_du() {
+        let (fs, _) = interpret(SAMPLE.lines());
+        let shallow = du(&fs, fs.root(), Some(1));
+        assert_eq!(paths(&fs, &shallow.iter().map(|x| x.0).collect::<Vec<_>>()), vec!["/a", "/d", "/"]);
+        assert_eq!(shallow.iter().map(|x| x.1).collect::<Vec<_>>(), vec![94853, 24933642, 48381165]);
+        assert_eq!(du(&fs, fs.root(), None).len(), 4);
+        assert_eq!(du(&fs, fs.lookup("/a").unwrap(), Some(0)), vec![(fs.lookup("/a").unwrap(), 94853)]);
+    }
+
+    #[test]
+    fn test_find() {
+        let (fs, _) = interpret(SAMPLE.lines());
+        let query = FindQuery { name: Some(Pattern::new("*.*").unwrap()), ..Default::default() };
+        assert_eq!(paths(&fs, &find(&fs, fs.root(), &query)), vec!["/a/h.lst", "/b.txt", "/c.dat", "/d/d.ext", "/d/d.log"]);
+
+        let query = FindQuery {
+            sizes: vec!["+5000000".parse().unwrap(), "-9000000".parse().unwrap()],
+            kind: Some(Kind::File),
+            ..Default::default()
+        };
+        assert_eq!(paths(&fs, &find(&fs, fs.root(), &query)), vec!["/c.dat", "/d/d.ext", "/d/d.log", "/d/k"]);
+
+        let query = FindQuery { sizes: vec!["584".parse().unwrap()], ..Default::default() };
+        assert_eq!(paths(&fs, &find(&fs, fs.root(), &query)), vec!["/a/e", "/a/e/i"]);
+        assert!("+x".parse::<SizeFilter>().is_err());
+    }
+
+    #[test]
+    fn test_largest() {
+        let (fs, _) = interpret(SAMPLE.lines());
+        assert_eq!(paths(&fs, &largest(&fs, 2, Kind::File)), vec!["/b.txt", "/c.dat"]);
+        assert_eq!(paths(&fs, &largest(&fs, 10, Kind::Directory)), vec!["/", "/d", "/a", "/a/e"]);
+    }
+}
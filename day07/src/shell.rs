@@ -0,0 +1,307 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+
+use crate::vfs::{FsError, NodeId, VirtualFs};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WarningKind {
+    /// The filesystem disagreed with itself: conflicting sizes, or a file and directory clash.
+    Fs(FsError),
+    /// `cd` into a directory that no `ls` had shown us. It's created anyway.
+    UnlistedDirectory(String),
+    /// `cd ..` from the root, which stays at the root.
+    AboveRoot,
+    /// A `$` line we don't know how to run.
+    UnknownCommand(String),
+    /// A line that isn't a command, and doesn't follow one that prints anything.
+    UnexpectedOutput(String),
+    /// A line of `ls` output that isn't `dir <name>` or `<size> <name>`.
+    BadListing(String),
+    /// `pwd` printed somewhere other than where we thought we were.
+    WrongDirectory { expected: String, found: String },
+    /// A second `ls` of a directory didn't show something the first one did.
+    MissingFromListing(String)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub line : usize,
+    pub kind : WarningKind
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            WarningKind::Fs(e) => write!(f, "{}", e),
+            WarningKind::UnlistedDirectory(path) => write!(f, "cd into {}, which was never listed", path),
+            WarningKind::AboveRoot => write!(f, "cd .. from /"),
+            WarningKind::UnknownCommand(command) => write!(f, "unknown command '{}'", command),
+            WarningKind::UnexpectedOutput(text) => write!(f, "output '{}' without a command", text),
+            WarningKind::BadListing(text) => write!(f, "can't understand ls output '{}'", text),
+            WarningKind::WrongDirectory { expected, found } =>
+                write!(f, "pwd printed {}, but we should be in {}", found, expected),
+            WarningKind::MissingFromListing(path) => write!(f, "{} is missing from a repeated ls", path),
+        }
+    }
+}
+
+/// What the lines after the last command are expected to be.
+#[derive(Debug)]
+enum Output {
+    Nothing,
+    Listing { dir: NodeId, names: BTreeSet<String> },
+    WorkingDirectory
+}
+
+/// Replays a terminal transcript to rebuild the filesystem it was exploring.
+///
+/// Understands `cd` (to `/`, `..`, or any relative or absolute path), `ls` and `pwd`. Rather
+/// than giving up on anything odd, it carries on and collects warnings with line numbers.
+#[derive(Debug)]
+pub struct Shell {
+    fs : VirtualFs,
+    cwd : NodeId,
+    output : Output,
+    listed : HashSet<NodeId>,
+    warnings : Vec<Warning>,
+    last_line : usize
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shell {
+    pub fn new() -> Self {
+        let fs = VirtualFs::new();
+        let cwd = fs.root();
+        Self { fs, cwd, output: Output::Nothing, listed: HashSet::new(), warnings: Vec::new(), last_line: 0 }
+    }
+
+    fn warn(&mut self, line: usize, kind: WarningKind) {
+        self.warnings.push(Warning { line, kind });
+    }
+
+    /// Feed in the next line of the transcript, numbered from 1.
+    pub fn feed(&mut self, line_number: usize, line: &str) {
+        self.last_line = line_number;
+        match line.strip_prefix("$ ") {
+            Some(command) => {
+                self.finish_output(line_number);
+                self.run(line_number, command.trim());
+            },
+            None if line.trim().is_empty() => { },
+            None => self.read_output(line_number, line.trim())
+        }
+    }
+
+    fn run(&mut self, line_number: usize, command: &str) {
+        let args : Vec<&str> = command.split_whitespace().collect();
+        match args[..] {
+            ["cd"] | ["cd", "/"] => { self.cwd = self.fs.root(); },
+            ["cd", path] => self.cd(line_number, path),
+            ["ls"] => {
+                self.output = Output::Listing { dir: self.cwd, names: BTreeSet::new() };
+            },
+            ["pwd"] => { self.output = Output::WorkingDirectory; },
+            _ => self.warn(line_number, WarningKind::UnknownCommand(command.to_string()))
+        }
+    }
+
+    fn cd(&mut self, line_number: usize, path: &str) {
+        let mut dir = if path.starts_with('/') { self.fs.root() } else { self.cwd };
+        for part in path.split('/') {
+            dir = match part {
+                "" | "." => dir,
+                ".." => match self.fs.parent(dir) {
+                    Some(parent) => parent,
+                    None => {
+                        self.warn(line_number, WarningKind::AboveRoot);
+                        dir
+                    }
+                },
+                name => match self.fs.child(dir, name) {
+                    Some(child) if self.fs.is_dir(child) => child,
+                    Some(child) => {
+                        let path = self.fs.path(child);
+                        self.warn(line_number, WarningKind::Fs(FsError::NotADirectory(path)));
+                        return;
+                    },
+                    None => match self.fs.mkdir(dir, name) {
+                        Ok(child) => {
+                            self.warn(line_number, WarningKind::UnlistedDirectory(self.fs.path(child)));
+                            child
+                        },
+                        Err(e) => {
+                            self.warn(line_number, WarningKind::Fs(e));
+                            return;
+                        }
+                    }
+                }
+            };
+        }
+        self.cwd = dir;
+    }
+
+    fn read_output(&mut self, line_number: usize, line: &str) {
+        match &mut self.output {
+            Output::Nothing => self.warn(line_number, WarningKind::UnexpectedOutput(line.to_string())),
+            Output::WorkingDirectory => {
+                let expected = self.fs.path(self.cwd);
+                if line.trim_end_matches('/') != expected.trim_end_matches('/') {
+                    self.warn(line_number, WarningKind::WrongDirectory { expected, found: line.to_string() });
+                }
+                self.output = Output::Nothing;
+            },
+            Output::Listing { dir, names } => {
+                let dir = *dir;
+                let result = match line.split_once(' ') {
+                    Some(("dir", name)) => {
+                        names.insert(name.to_string());
+                        self.fs.mkdir(dir, name)
+                    },
+                    Some((size, name)) if size.parse::<usize>().is_ok() => {
+                        names.insert(name.to_string());
+                        self.fs.add_file(dir, name, size.parse().unwrap())
+                    },
+                    _ => {
+                        self.warn(line_number, WarningKind::BadListing(line.to_string()));
+                        return;
+                    }
+                };
+                if let Err(e) = result {
+                    self.warn(line_number, WarningKind::Fs(e));
+                }
+            }
+        }
+    }
+
+    /// Wrap up whatever the previous command printed, which ended just before `line_number`.
+    fn finish_output(&mut self, line_number: usize) {
+        if let Output::Listing { dir, names } = std::mem::replace(&mut self.output, Output::Nothing) {
+            if !self.listed.insert(dir) {
+                let missing : Vec<NodeId> = self.fs.children(dir)
+                    .filter(|&child| !names.contains(self.fs.node(child).name()))
+                    .collect();
+                for child in missing {
+                    self.warn(line_number - 1, WarningKind::MissingFromListing(self.fs.path(child)));
+                }
+            }
+        }
+    }
+
+    /// The filesystem as rebuilt so far, and every warning along the way.
+    pub fn finish(mut self) -> (VirtualFs, Vec<Warning>) {
+        self.finish_output(self.last_line + 1);
+        (self.fs, self.warnings)
+    }
+}
+
+/// Rebuild the filesystem explored by a whole transcript.
+pub fn interpret<S: AsRef<str>>(lines: impl IntoIterator<Item = S>) -> (VirtualFs, Vec<Warning>) {
+    let mut shell = Shell::new();
+    for (idx, line) in lines.into_iter().enumerate() {
+        shell.feed(idx + 1, line.as_ref());
+    }
+    shell.finish()
+}
+
+/// The example transcript from the puzzle, for tests.
+#[cfg(test)]
+pub(crate) const SAMPLE: &str = "$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k";
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+
+    #[test]
+    fn test_sample() {
+        let (fs, warnings) = interpret(SAMPLE.lines());
+        assert_eq!(warnings, vec![]);
+        assert_eq!(fs.size(fs.root()), 48381165);
+        assert_eq!(fs.size(fs.lookup("/a/e").unwrap()), 584);
+    }
+
+    #[test]
+    fn test_paths_and_pwd() {
+        let extra = "
+$ cd /a/e
+$ pwd
+/a/e
+$ cd ../../d
+$ pwd
+/d
+$ cd /
+$ cd a/e/../e
+$ pwd
+/a
+$ ls
+584 i";
+        let (fs, warnings) = interpret(SAMPLE.lines().chain(extra.lines()));
+        assert_eq!(warnings, vec![Warning {
+            line: 34,
+            kind: WarningKind::WrongDirectory { expected: "/a/e".to_string(), found: "/a".to_string() }
+        }]);
+        assert_eq!(fs.size(fs.root()), 48381165);
+    }
+
+    #[test]
+    fn test_inconsistencies() {
+        let transcript = "$ cd /
+$ ls
+dir a
+10 b
+$ ls
+dir a
+20 b
+dir b
+$ ls
+10 b
+$ cd b
+$ cd ..
+$ cd ..
+$ cd x/y
+$ rm -rf /
+oops";
+        let (fs, warnings) = interpret(transcript.lines());
+        let kinds : Vec<(usize, WarningKind)> = warnings.into_iter().map(|w| (w.line, w.kind)).collect();
+        assert_eq!(kinds, vec![
+            (7, WarningKind::Fs(FsError::SizeConflict { path: "/b".to_string(), existing: 10, new: 20 })),
+            (8, WarningKind::Fs(FsError::NameClash("/b".to_string()))),
+            (10, WarningKind::MissingFromListing("/a".to_string())),
+            (11, WarningKind::Fs(FsError::NotADirectory("/b".to_string()))),
+            (12, WarningKind::AboveRoot),
+            (13, WarningKind::AboveRoot),
+            (14, WarningKind::UnlistedDirectory("/x".to_string())),
+            (14, WarningKind::UnlistedDirectory("/x/y".to_string())),
+            (15, WarningKind::UnknownCommand("rm -rf /".to_string())),
+            (16, WarningKind::UnexpectedOutput("oops".to_string())),
+        ]);
+        assert_eq!(fs.size(fs.root()), 10);
+    }
+}
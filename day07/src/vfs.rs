@@ -0,0 +1,326 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Index of a node in a `VirtualFs`. Only meaningful for the filesystem that handed it out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+    Directory(BTreeMap<String, NodeId>),
+    File
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    name : String,
+    parent : Option<NodeId>,
+    // File size, or for directories the total of everything underneath, kept up to date on insert.
+    size : usize,
+    kind : NodeKind
+}
+
+impl Node {
+    pub fn name(&self) -> &str { &self.name }
+    pub fn parent(&self) -> Option<NodeId> { self.parent }
+    pub fn size(&self) -> usize { self.size }
+    pub fn is_dir(&self) -> bool { matches!(self.kind, NodeKind::Directory(_)) }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsError {
+    /// Tried to look inside something that's a file.
+    NotADirectory(String),
+    /// A file and a directory (or two files of different sizes) with the same path.
+    NameClash(String),
+    /// The same file was seen twice with different sizes.
+    SizeConflict { path: String, existing: usize, new: usize },
+    /// Names can't be empty, `.`, `..` or contain a `/`.
+    InvalidName(String)
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FsError::NotADirectory(path) => write!(f, "{} is not a directory", path),
+            FsError::NameClash(path) => write!(f, "{} is both a file and a directory", path),
+            FsError::SizeConflict { path, existing, new } =>
+                write!(f, "{} was listed with size {}, now {}", path, existing, new),
+            FsError::InvalidName(name) => write!(f, "'{}' is not a valid name", name),
+        }
+    }
+}
+
+impl std::error::Error for FsError {}
+
+/// A filesystem tree held in one arena, so every node can point at its parent.
+///
+/// Node 0 is always the root, `/`. Directory sizes are cached and updated whenever a file is
+/// added, so `size()` is O(1). Directories exist explicitly, even if they're empty.
+#[derive(Debug, Clone)]
+pub struct VirtualFs {
+    nodes : Vec<Node>
+}
+
+impl Default for VirtualFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualFs {
+    pub fn new() -> Self {
+        let root = Node { name: String::new(), parent: None, size: 0, kind: NodeKind::Directory(BTreeMap::new()) };
+        Self { nodes: vec![root] }
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn size(&self, id: NodeId) -> usize {
+        self.nodes[id.0].size
+    }
+
+    pub fn is_dir(&self, id: NodeId) -> bool {
+        self.nodes[id.0].is_dir()
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].parent
+    }
+
+    /// Number of nodes, including the root.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1
+    }
+
+    /// Children of a directory, sorted by name. Files have none.
+    pub fn children(&self, id: NodeId) -> impl DoubleEndedIterator<Item = NodeId> + '_ {
+        let children = match &self.nodes[id.0].kind {
+            NodeKind::Directory(children) => Some(children.values().copied()),
+            NodeKind::File => None
+        };
+        children.into_iter().flatten()
+    }
+
+    pub fn child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        match &self.nodes[id.0].kind {
+            NodeKind::Directory(children) => children.get(name).copied(),
+            NodeKind::File => None
+        }
+    }
+
+    /// Absolute path, like `/a/b/c`.
+    pub fn path(&self, id: NodeId) -> String {
+        let mut names = Vec::new();
+        let mut current = Some(id);
+        while let Some(node) = current {
+            names.push(self.nodes[node.0].name.as_str());
+            current = self.nodes[node.0].parent;
+        }
+        if names.len() == 1 { return "/".to_string(); }
+        names.into_iter().rev().collect::<Vec<_>>().join("/")
+    }
+
+    /// Find a node by path. Absolute paths start at the root, anything else starts at `from`.
+    /// `.` and `..` work as usual, and `..` at the root stays at the root.
+    pub fn resolve(&self, from: NodeId, path: &str) -> Option<NodeId> {
+        let mut current = if path.starts_with('/') { self.root() } else { from };
+        for part in path.split('/') {
+            current = match part {
+                "" | "." => current,
+                ".." => self.parent(current).unwrap_or(current),
+                name => self.child(current, name)?
+            };
+        }
+        Some(current)
+    }
+
+    /// Find a node by absolute path, like `/a/b/c`.
+    pub fn lookup(&self, path: &str) -> Option<NodeId> {
+        self.resolve(self.root(), path)
+    }
+
+    fn check_name(name: &str) -> Result<(), FsError> {
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(FsError::InvalidName(name.to_string()));
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, parent: NodeId, name: &str, size: usize, kind: NodeKind) -> Result<NodeId, FsError> {
+        let id = NodeId(self.nodes.len());
+        match &mut self.nodes[parent.0].kind {
+            NodeKind::Directory(children) => { children.insert(name.to_string(), id); },
+            NodeKind::File => return Err(FsError::NotADirectory(self.path(parent)))
+        }
+        self.nodes.push(Node { name: name.to_string(), parent: Some(parent), size, kind });
+
+        let mut ancestor = Some(parent);
+        while let Some(dir) = ancestor {
+            self.nodes[dir.0].size += size;
+            ancestor = self.nodes[dir.0].parent;
+        }
+        Ok(id)
+    }
+
+    /// Create a directory, or return the existing one of that name.
+    pub fn mkdir(&mut self, parent: NodeId, name: &str) -> Result<NodeId, FsError> {
+        Self::check_name(name)?;
+        match self.child(parent, name) {
+            Some(id) if self.is_dir(id) => Ok(id),
+            Some(id) => Err(FsError::NameClash(self.path(id))),
+            None => self.insert(parent, name, 0, NodeKind::Directory(BTreeMap::new()))
+        }
+    }
+
+    /// Create a file. Adding the same file again with the same size is fine.
+    pub fn add_file(&mut self, parent: NodeId, name: &str, size: usize) -> Result<NodeId, FsError> {
+        Self::check_name(name)?;
+        match self.child(parent, name) {
+            Some(id) if self.is_dir(id) => Err(FsError::NameClash(self.path(id))),
+            Some(id) if self.size(id) != size =>
+                Err(FsError::SizeConflict { path: self.path(id), existing: self.size(id), new: size }),
+            Some(id) => Ok(id),
+            None => self.insert(parent, name, size, NodeKind::File)
+        }
+    }
+
+    /// Every node under `start` (inclusive), each directory before its contents.
+    pub fn pre_order(&self, start: NodeId) -> PreOrder<'_> {
+        PreOrder { fs: self, stack: vec![start] }
+    }
+
+    /// Every node under `start` (inclusive), each directory after its contents.
+    pub fn post_order(&self, start: NodeId) -> PostOrder<'_> {
+        PostOrder { fs: self, stack: vec![(start, false)] }
+    }
+
+    /// Every directory, from the root down.
+    pub fn directories(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.pre_order(self.root()).filter(|&id| self.is_dir(id))
+    }
+}
+
+pub struct PreOrder<'a> {
+    fs : &'a VirtualFs,
+    stack : Vec<NodeId>
+}
+
+impl Iterator for PreOrder<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.stack.pop()?;
+        // Reversed, so children come off the stack in name order.
+        self.stack.extend(self.fs.children(id).rev());
+        Some(id)
+    }
+}
+
+pub struct PostOrder<'a> {
+    fs : &'a VirtualFs,
+    // Each node, and whether its children have been pushed yet.
+    stack : Vec<(NodeId, bool)>
+}
+
+impl Iterator for PostOrder<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (id, expanded) = self.stack.pop()?;
+            if expanded {
+                return Some(id);
+            }
+            self.stack.push((id, true));
+            self.stack.extend(self.fs.children(id).rev().map(|child| (child, false)));
+        }
+    }
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+
+    fn sample() -> VirtualFs {
+        let mut fs = VirtualFs::new();
+        let root = fs.root();
+        let a = fs.mkdir(root, "a").unwrap();
+        let e = fs.mkdir(a, "e").unwrap();
+        fs.add_file(e, "i", 584).unwrap();
+        fs.add_file(a, "f", 29116).unwrap();
+        fs.add_file(a, "g", 2557).unwrap();
+        fs.add_file(a, "h.lst", 62596).unwrap();
+        fs.add_file(root, "b.txt", 14848514).unwrap();
+        fs.add_file(root, "c.dat", 8504156).unwrap();
+        let d = fs.mkdir(root, "d").unwrap();
+        fs.add_file(d, "j", 4060174).unwrap();
+        fs.add_file(d, "d.log", 8033020).unwrap();
+        fs.add_file(d, "d.ext", 5626152).unwrap();
+        fs.add_file(d, "k", 7214296).unwrap();
+        fs.mkdir(d, "empty").unwrap();
+        fs
+    }
+
+    #[test]
+    fn test_sizes_and_paths() {
+        let fs = sample();
+        assert_eq!(fs.size(fs.root()), 48381165);
+        assert_eq!(fs.size(fs.lookup("/a").unwrap()), 94853);
+        assert_eq!(fs.size(fs.lookup("/a/e").unwrap()), 584);
+        assert_eq!(fs.size(fs.lookup("/d").unwrap()), 24933642);
+
+        let empty = fs.lookup("/d/empty").unwrap();
+        assert!(fs.is_dir(empty));
+        assert_eq!(fs.size(empty), 0);
+        assert_eq!(fs.path(empty), "/d/empty");
+        assert_eq!(fs.path(fs.root()), "/");
+
+        let a = fs.lookup("/a").unwrap();
+        assert_eq!(fs.resolve(a, "e/i"), fs.lookup("/a/e/i"));
+        assert_eq!(fs.resolve(a, "../d/./k"), fs.lookup("/d/k"));
+        assert_eq!(fs.resolve(a, "/b.txt"), fs.lookup("/b.txt"));
+        assert_eq!(fs.lookup("/a/nope"), None);
+        assert_eq!(fs.lookup("/b.txt/x"), None);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let mut fs = sample();
+        let root = fs.root();
+        assert!(fs.add_file(root, "b.txt", 14848514).is_ok());
+        assert_eq!(
+            fs.add_file(root, "b.txt", 1),
+            Err(FsError::SizeConflict { path: "/b.txt".to_string(), existing: 14848514, new: 1 })
+        );
+        assert_eq!(fs.mkdir(root, "b.txt"), Err(FsError::NameClash("/b.txt".to_string())));
+        assert_eq!(fs.add_file(root, "a", 1), Err(FsError::NameClash("/a".to_string())));
+        assert_eq!(fs.mkdir(root, ".."), Err(FsError::InvalidName("..".to_string())));
+        assert_eq!(fs.size(root), 48381165);
+    }
+
+    #[test]
+    fn test_traversal() {
+        let fs = sample();
+        let names = |ids: Vec<NodeId>| ids.iter().map(|&id| fs.node(id).name().to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            names(fs.pre_order(fs.root()).collect()),
+            vec!["", "a", "e", "i", "f", "g", "h.lst", "b.txt", "c.dat", "d", "d.ext", "d.log", "empty", "j", "k"]
+        );
+        assert_eq!(
+            names(fs.post_order(fs.lookup("/a").unwrap()).collect()),
+            vec!["i", "e", "f", "g", "h.lst", "a"]
+        );
+        assert_eq!(fs.directories().count(), 5);
+    }
+}
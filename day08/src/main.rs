@@ -1,152 +1,394 @@
-// This implementation is awful and I stopped caring at some point.
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
 
-use std::fs::File;
-use std::io::{self, prelude::*, BufReader};
-use itertools::izip;
-use std::iter::zip;
+mod render;
+
+use render::HeatMap;
 
 type HeightMap = Vec<Vec<u8>>;
 
+/// Directions to look in, as (row, column) steps.
+const FOUR_WAYS: [(isize, isize); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+const EIGHT_WAYS: [(isize, isize); 8] = [(0, -1), (0, 1), (-1, 0), (1, 0), (-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+/// Which trees get in the way of a view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Blocking {
+    /// Anything at least as tall as the viewer, as in the puzzle.
+    TallerOrEqual,
+    /// Only trees taller than the viewer.
+    StrictlyTaller,
+    /// Only trees more than this much taller than the viewer.
+    SeeOver(u8),
+}
+
+impl std::str::FromStr for Blocking {
+    type Err = String;
+
+    /// `equal`, `strict`, or `over=N`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "equal" => Ok(Blocking::TallerOrEqual),
+            "strict" => Ok(Blocking::StrictlyTaller),
+            _ => s.strip_prefix("over=")
+                .and_then(|n| n.parse().ok())
+                .map(Blocking::SeeOver)
+                .ok_or(format!("unknown blocking rule '{}'", s))
+        }
+    }
+}
+
+impl Blocking {
+    /// The shortest tree that blocks the view from a tree of height `viewer`.
+    fn threshold(&self, viewer: u8) -> u32 {
+        match *self {
+            Blocking::TallerOrEqual => viewer as u32,
+            Blocking::StrictlyTaller => viewer as u32 + 1,
+            Blocking::SeeOver(tolerance) => viewer as u32 + tolerance as u32 + 1,
+        }
+    }
+}
+
+/// Every straight line through the grid in direction `(dr, dc)`, each starting from the edge
+/// that direction looks towards and working back across the grid.
+fn sight_lines(rows: usize, cols: usize, (dr, dc): (isize, isize)) -> Vec<Vec<(usize, usize)>> {
+    assert!((dr, dc) != (0, 0), "Looking nowhere would take forever.");
+    let step = |(r, c): (usize, usize), (dr, dc): (isize, isize)| {
+        let (r, c) = (r.checked_add_signed(dr)?, c.checked_add_signed(dc)?);
+        (r < rows && c < cols).then_some((r, c))
+    };
+
+    let mut lines = Vec::new();
+    for r in 0..rows {
+        for c in 0..cols {
+            // Lines start at trees with nothing further in the direction we're looking.
+            if step((r, c), (dr, dc)).is_some() { continue; }
+            lines.push(std::iter::successors(Some((r, c)), |&cell| step(cell, (-dr, -dc))).collect());
+        }
+    }
+    lines
+}
+
 #[derive(Default, Debug)]
 struct VisibilityMaps {
-    left : HeightMap,
-    right : HeightMap,
-    top : HeightMap,
-    bottom : HeightMap,
+    directions : Vec<(isize, isize)>,
+    // How many trees each tree can see in each direction, one map per entry in `directions`.
+    distances : Vec<Vec<Vec<usize>>>,
+    // Whether each tree can be seen from outside the forest, from any direction.
+    visible : Vec<Vec<bool>>,
+    // Product of how far each tree can see in every direction. It stops at u64::MAX rather than
+    // overflowing, which eight directions across a forest a few hundred trees wide can reach.
+    scenic : Vec<Vec<u64>>,
 }
 
 impl VisibilityMaps {
-    fn from_height_map(height: &HeightMap) -> Self {
-        let mut left : HeightMap = Vec::new();
-        let mut right : HeightMap = Vec::new();
-        let mut top : HeightMap = Vec::new();
-        let mut bottom : HeightMap = Vec::new();
-
-        // First compute left / right / top visibility
-        let mut visible_from_top : Vec<u8> = vec![0; height[0].len()];
-        for row in height.iter() {
-            let mut visible_from_left : u8 = 0;
-            left.push(
-                row.iter().map(|c| {
-                    if c >= &visible_from_left {
-                        let previous = visible_from_left;
-                        visible_from_left = c + 1;
-                        return previous;
-                    } else {
-                        return visible_from_left
-                    }
-                }).collect()
-            );
-
-            let mut visible_from_right : u8 = 0;
-            let row_reversed = row.iter().rev().map(|x| *x).collect::<Vec<u8>>();
-            let zz : Vec<u8> = row_reversed.iter().map(|c| {
-                    if c >= &visible_from_right {
-                        let previous = visible_from_right;
-                        visible_from_right = c + 1;
-                        return previous;
-                    } else {
-                        return visible_from_right
-                    }
-                }).collect();
-            right.push(zz.iter().rev().map(|x| *x).collect());
+    /// Works along every line with a stack of the trees that could still block the view of
+    /// the ones to come. Their heights only go down towards the top, so the nearest one tall
+    /// enough to block a tree is a binary search away, whatever the blocking rule.
+    fn from_height_map(height: &HeightMap, directions: &[(isize, isize)], blocking: Blocking) -> Self {
+        let rows = height.len();
+        let cols = height.first().map_or(0, |row| row.len());
+        let mut visible = vec![vec![false; cols]; rows];
+        let mut scenic = vec![vec![1u64; cols]; rows];
+        let mut distances = Vec::new();
 
-            top.push(visible_from_top.clone());
-            for (r, v) in zip(row.iter(), visible_from_top.iter_mut()) {
-                if r >= v {
-                    *v = r + 1;
-                }
-            }
-        }
+        for &direction in directions {
+            let mut distance_map = vec![vec![0; cols]; rows];
+            for line in sight_lines(rows, cols, direction) {
+                let mut blockers : Vec<usize> = Vec::new();
+                for (idx, &(r, c)) in line.iter().enumerate() {
+                    let threshold = blocking.threshold(height[r][c]);
+                    let tall_enough = blockers.partition_point(|&b| {
+                        let (br, bc) = line[b];
+                        height[br][bc] as u32 >= threshold
+                    });
+                    let distance = match tall_enough.checked_sub(1) {
+                        Some(nearest) => idx - blockers[nearest],
+                        None => {
+                            visible[r][c] = true;
+                            idx
+                        }
+                    };
+                    distance_map[r][c] = distance;
+                    scenic[r][c] = scenic[r][c].saturating_mul(distance as u64);
 
-        // Now compute bottom visibility
-        let mut visible_from_bottom : Vec<u8> = vec![0; height[0].len()];
-        for row in height.iter().rev() {
-            bottom.push(visible_from_bottom.clone());
-            for (r, v) in zip(row.iter(), visible_from_bottom.iter_mut()) {
-                if r >= v {
-                    *v = r + 1;
+                    // Anything no taller than this tree is hidden behind it from now on.
+                    while let Some(&top) = blockers.last() {
+                        let (br, bc) = line[top];
+                        if height[br][bc] > height[r][c] { break; }
+                        blockers.pop();
+                    }
+                    blockers.push(idx);
                 }
             }
+            distances.push(distance_map);
         }
-        bottom.reverse();
 
-        Self {left, right, top, bottom}
+        Self { directions: directions.to_vec(), distances, visible, scenic }
+    }
+
+    /// How far each tree can see in one direction, if it's one of the ones we looked in.
+    fn distance_map(&self, direction: (isize, isize)) -> Option<&Vec<Vec<usize>>> {
+        let idx = self.directions.iter().position(|&d| d == direction)?;
+        Some(&self.distances[idx])
+    }
+
+    fn count_visible(&self) -> usize {
+        self.visible.iter().map(|row| row.iter().filter(|v| **v).count()).sum()
     }
 
-    fn overall_visibility(&self) -> HeightMap {
-        izip!(&self.left, &self.right, &self.top, &self.bottom).map(|(l, r, t, b)| {
-            izip!(l, r, t, b).map(|(lv, rv, tv, bv)| {
-                *[*lv, *rv, *tv, *bv].iter().min().unwrap()
-            }).collect()
-        }).collect()
+    fn best_scenic_score(&self) -> u64 {
+        self.scenic.iter().flatten().copied().max().unwrap_or(0)
     }
 
-    fn count_visible(&self, height: &HeightMap) -> usize {
-        let visibility = self.overall_visibility();
-        zip(height, visibility).map(|(h, v)| {
-            zip(h, v).filter(
-                |(hv, vv)| { *hv >= vv }
-            ).count()
-        }).sum()
+    /// Where the tree with the best scenic score is, picking the first in reading order on ties.
+    fn best_tree(&self) -> Option<(usize, usize)> {
+        let best = self.best_scenic_score();
+        self.scenic.iter().enumerate()
+            .find_map(|(r, row)| row.iter().position(|&s| s == best).map(|c| (r, c)))
     }
 }
 
-fn parse_height_map(filename: &str) -> HeightMap {
-    let file = File::open(filename).expect(format!("File {} not readable", filename).as_str());
-    let height_map : HeightMap = BufReader::new(file)
-        .lines() // Get a line iterator
-        .filter_map(|line| line.ok())
+fn parse_height_map(text: &str) -> HeightMap {
+    let height_map : HeightMap = text.lines()
         .map(|line| line.chars().map(|c| c.to_digit(10).unwrap() as u8).collect())
         .collect();
-    let first_line_length = height_map[0].len();
+    let first_line_length = height_map.first().map_or(0, |row| row.len());
     assert!(height_map.iter().all(|x| x.len() == first_line_length));
     height_map
 }
 
-fn get_scenic_scores(height: &HeightMap) -> Vec<Vec<u32>> {
-    let h = height.len();
-    let w = height[0].len();
-    let mut scores : Vec<Vec<u32>> = vec![vec![0; w]; h];
-    for vr in 0..h {
-        for vc in 0..w {
-            let value = height[vr][vc];
-            let mut left = 0;
-            for cc in (0..vc).rev() {
-                left += 1;
-                if height[vr][cc] >= value { break; }
-            }
-            let mut right = 0;
-            for cc in vc+1..w {
-                right += 1;
-                if height[vr][cc] >= value { break; }
-            }
-            let mut top = 0;
-            for cr in (0..vr).rev() {
-                top += 1;
-                if height[cr][vc] >= value { break; }
-            }
-            let mut bottom = 0;
-            for cr in vr+1..w {
-                bottom += 1;
-                if height[cr][vc] >= value { break; }
-            }
+fn day08() {
+    let text = fs::read_to_string("input.txt").expect("File 'input.txt' not readable.");
+    let height = parse_height_map(&text);
+    let visibility_maps = VisibilityMaps::from_height_map(&height, &FOUR_WAYS, Blocking::TallerOrEqual);
+
+    println!("Part 1: {:?}", visibility_maps.count_visible());
+    println!("Part 2: {:?}", visibility_maps.best_scenic_score());
+}
 
-            scores[vr][vc] = left * right * top * bottom;
+/// Parse `[4|8|dr,dc;dr,dc;...] [equal|strict|over=N]`, in either order.
+fn parse_rules(args: &[String]) -> Result<(Vec<(isize, isize)>, Blocking), String> {
+    let mut directions = FOUR_WAYS.to_vec();
+    let mut blocking = Blocking::TallerOrEqual;
+    for arg in args {
+        match arg.as_str() {
+            "4" => directions = FOUR_WAYS.to_vec(),
+            "8" => directions = EIGHT_WAYS.to_vec(),
+            rays if rays.contains(',') => {
+                directions = rays.split(';').map(|ray| {
+                    let (dr, dc) = ray.split_once(',').ok_or(format!("bad ray '{}'", ray))?;
+                    let step = (dr.trim().parse().map_err(|_| format!("bad ray '{}'", ray))?,
+                        dc.trim().parse().map_err(|_| format!("bad ray '{}'", ray))?);
+                    if step == (0, 0) { return Err("a ray has to go somewhere".to_string()); }
+                    Ok(step)
+                }).collect::<Result<_, String>>()?;
+            },
+            rule => blocking = rule.parse()?
         }
     }
-    scores
+    Ok((directions, blocking))
 }
 
-fn day08() {
-    let height = parse_height_map("input.txt");
-    let visibility_maps = VisibilityMaps::from_height_map(&height);
+/// Visibility under other sight-line rules, with how far the best tree sees each way.
+fn rules(args: &[String]) -> Result<(), String> {
+    let (directions, blocking) = parse_rules(args)?;
+    let text = fs::read_to_string("input.txt").expect("File 'input.txt' not readable.");
+    let height = parse_height_map(&text);
+    let maps = VisibilityMaps::from_height_map(&height, &directions, blocking);
 
-    println!("Part 1: {:?}", visibility_maps.count_visible(&height));
-    println!("Part 2: {:?}", get_scenic_scores(&height).iter().map(|x| x.iter().max().unwrap()).max().unwrap());
+    println!("{} visible, best scenic score {}", maps.count_visible(), maps.best_scenic_score());
+    if let Some((r, c)) = maps.best_tree() {
+        println!("Best tree at row {}, column {} (height {}) sees:", r, c, height[r][c]);
+        for &direction in &directions {
+            println!("  {:?}: {}", direction, maps.distance_map(direction).unwrap()[r][c]);
+        }
+    }
+    Ok(())
+}
+
+/// Write the height map, visibility mask and scenic scores as PNG heat maps into `dir`.
+fn write_pngs(dir: &Path, scale: usize) -> io::Result<()> {
+    let text = fs::read_to_string("input.txt").expect("File 'input.txt' not readable.");
+    let height = parse_height_map(&text);
+    let maps = VisibilityMaps::from_height_map(&height, &FOUR_WAYS, Blocking::TallerOrEqual);
+    let best = maps.best_tree();
+
+    fs::create_dir_all(dir)?;
+    for (name, heat_map) in [
+        ("heights.png", HeatMap::heights(&height)),
+        ("visible.png", HeatMap::visibility(&maps.visible)),
+        ("scenic.png", HeatMap::scenic(&maps.scenic)),
+    ] {
+        let path = dir.join(name);
+        heat_map.with_highlight(best).write_png(BufWriter::new(File::create(&path)?), scale)?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+/// Draw one of the maps in the terminal, with the best tree picked out.
+fn show(layer: &str) -> Result<(), String> {
+    let text = fs::read_to_string("input.txt").expect("File 'input.txt' not readable.");
+    let height = parse_height_map(&text);
+    let maps = VisibilityMaps::from_height_map(&height, &FOUR_WAYS, Blocking::TallerOrEqual);
+    let heat_map = match layer {
+        "height" => HeatMap::heights(&height),
+        "visible" => HeatMap::visibility(&maps.visible),
+        "scenic" => HeatMap::scenic(&maps.scenic),
+        _ => return Err(format!("unknown layer '{}'", layer)),
+    };
+    print!("{}", heat_map.with_highlight(maps.best_tree()).to_terminal());
+    Ok(())
+}
+
+const USAGE: &str = "usage: day08
+       day08 --rules [4|8|dr,dc;dr,dc...] [equal|strict|over=N]
+       day08 --png DIR [SCALE]
+       day08 --show height|visible|scenic";
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
 }
 
 fn main() -> io::Result<()> {
-    day08();
+    let args : Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|s| s.as_str()) {
+        Some("--rules") => if let Err(e) = rules(&args[1..]) { fail(e) },
+        Some("--png") => {
+            let dir = args.get(1).unwrap_or_else(|| fail("--png needs a directory"));
+            let scale = match args.get(2) {
+                Some(scale) => scale.parse().unwrap_or_else(|_| fail(format!("bad scale '{}'", scale))),
+                None => 8,
+            };
+            write_pngs(Path::new(dir), scale)?;
+        },
+        Some("--show") => if let Err(e) = show(args.get(1).map_or("", |s| s.as_str())) { fail(e) },
+        _ => day08(),
+    }
     Ok(())
 }
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+
+    const SAMPLE: &str = "30373\n25512\n65332\n33549\n35390\n";
+
+    /// The old walk-every-direction approach, to check against.
+    fn brute_force(height: &HeightMap, directions: &[(isize, isize)], blocking: Blocking) -> (Vec<Vec<bool>>, Vec<Vec<u64>>) {
+        let (rows, cols) = (height.len() as isize, height[0].len() as isize);
+        let mut visible = vec![vec![false; cols as usize]; rows as usize];
+        let mut scenic = vec![vec![1u64; cols as usize]; rows as usize];
+        for r in 0..rows {
+            for c in 0..cols {
+                let value = height[r as usize][c as usize];
+                for &(dr, dc) in directions {
+                    let (mut cr, mut cc, mut distance) = (r + dr, c + dc, 0);
+                    let mut blocked = false;
+                    while cr >= 0 && cr < rows && cc >= 0 && cc < cols {
+                        distance += 1;
+                        if height[cr as usize][cc as usize] as u32 >= blocking.threshold(value) { blocked = true; break; }
+                        cr += dr;
+                        cc += dc;
+                    }
+                    visible[r as usize][c as usize] |= !blocked;
+                    scenic[r as usize][c as usize] = scenic[r as usize][c as usize].saturating_mul(distance);
+                }
+            }
+        }
+        (visible, scenic)
+    }
+
+    #[test]
+    fn test_scenic_saturates() {
+        // A flat forest where nothing blocks anything: the middle tree sees 300 trees each way.
+        let height = vec![vec![0; 601]; 601];
+        let maps = VisibilityMaps::from_height_map(&height, &EIGHT_WAYS, Blocking::StrictlyTaller);
+        assert_eq!(maps.scenic[300][300], u64::MAX);
+        assert_eq!(maps.best_scenic_score(), u64::MAX);
+        assert_eq!(maps.scenic[0][300], 0);
+        assert_eq!(maps.scenic[100][100], 100u64.pow(5) * 500u64.pow(3));
+    }
+
+    #[test]
+    fn test_sample() {
+        let height = parse_height_map(SAMPLE);
+        let maps = VisibilityMaps::from_height_map(&height, &FOUR_WAYS, Blocking::TallerOrEqual);
+        assert_eq!(maps.count_visible(), 21);
+        assert_eq!(maps.best_scenic_score(), 8);
+        assert_eq!(VisibilityMaps::from_height_map(&height, &FOUR_WAYS, Blocking::TallerOrEqual).scenic[3][2], 8);
+    }
+
+    #[test]
+    fn test_non_square() {
+        for text in ["3037325\n2551212\n6533208", "303\n255\n653\n335\n353\n899\n102", "12345678", "1\n9\n1\n9", ""] {
+            let height = parse_height_map(text);
+            let maps = VisibilityMaps::from_height_map(&height, &FOUR_WAYS, Blocking::TallerOrEqual);
+            if height.is_empty() {
+                assert_eq!(maps.count_visible(), 0);
+                continue;
+            }
+            let (visible, scenic) = brute_force(&height, &FOUR_WAYS, Blocking::TallerOrEqual);
+            assert_eq!(maps.visible, visible, "visibility for {:?}", text);
+            assert_eq!(maps.scenic, scenic, "scenic scores for {:?}", text);
+        }
+
+        // A tall tree near the bottom of a narrow forest used to be cut short by the width.
+        let height = parse_height_map("11\n11\n91\n11\n11\n11");
+        assert_eq!(VisibilityMaps::from_height_map(&height, &FOUR_WAYS, Blocking::TallerOrEqual).scenic[2][0], 0);
+        assert_eq!(VisibilityMaps::from_height_map(&height, &FOUR_WAYS, Blocking::TallerOrEqual).scenic[2][1], 0);
+        let height = parse_height_map("111\n191\n111\n111\n111");
+        assert_eq!(VisibilityMaps::from_height_map(&height, &FOUR_WAYS, Blocking::TallerOrEqual).scenic[1][1], 3); // 1 left, 1 right, 1 up, 3 down
+    }
+
+    #[test]
+    fn test_rules() {
+        let texts = [SAMPLE, "3037325\n2551212\n6533208", "303\n255\n653\n335\n353\n899\n102"];
+        let rays : [&[(isize, isize)]; 3] = [&FOUR_WAYS, &EIGHT_WAYS, &[(1, 2), (-2, -1), (0, 3)]];
+        let rules = [Blocking::TallerOrEqual, Blocking::StrictlyTaller, Blocking::SeeOver(1), Blocking::SeeOver(3)];
+        for text in texts {
+            let height = parse_height_map(text);
+            for directions in rays {
+                for blocking in rules {
+                    let maps = VisibilityMaps::from_height_map(&height, directions, blocking);
+                    let (visible, scenic) = brute_force(&height, directions, blocking);
+                    assert_eq!(maps.visible, visible, "{:?} {:?} {:?}", text, directions, blocking);
+                    assert_eq!(maps.scenic, scenic, "{:?} {:?} {:?}", text, directions, blocking);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_distance_maps() {
+        let height = parse_height_map(SAMPLE);
+        let maps = VisibilityMaps::from_height_map(&height, &EIGHT_WAYS, Blocking::TallerOrEqual);
+        assert_eq!(maps.distances.len(), 8);
+        // The middle 5 in the fourth row, from the puzzle: 2 up, 2 left, 2 down, 2 right.
+        assert_eq!(maps.distance_map((-1, 0)).unwrap()[3][2], 2);
+        assert_eq!(maps.distance_map((0, -1)).unwrap()[3][2], 2);
+        // Diagonally up-left it sees the 5 at (2,1), up-right it sees past the 3 at (2,3) to the 1 at (1,4).
+        assert_eq!(maps.distance_map((-1, -1)).unwrap()[3][2], 1);
+        assert_eq!(maps.distance_map((-1, 1)).unwrap()[3][2], 2);
+        assert_eq!(maps.distance_map((2, 2)), None);
+
+        let strict = VisibilityMaps::from_height_map(&height, &FOUR_WAYS, Blocking::StrictlyTaller);
+        assert_eq!(strict.distance_map((0, -1)).unwrap()[1][2], 2); // sees past the other 5
+        assert_eq!(maps.best_tree(), Some((3, 2)));
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+        assert_eq!(parse_rules(&args("")), Ok((FOUR_WAYS.to_vec(), Blocking::TallerOrEqual)));
+        assert_eq!(parse_rules(&args("over=2 8")), Ok((EIGHT_WAYS.to_vec(), Blocking::SeeOver(2))));
+        assert_eq!(parse_rules(&args("1,2;-2,-1 strict")), Ok((vec![(1, 2), (-2, -1)], Blocking::StrictlyTaller)));
+        assert!(parse_rules(&args("0,0")).is_err());
+        assert!(parse_rules(&args("over=x")).is_err());
+    }
+}
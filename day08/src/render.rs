@@ -0,0 +1,184 @@
+use std::io::{self, Write};
+
+use colored::*;
+
+pub type Rgb = (u8, u8, u8);
+
+/// Stops along the colour scale from low to high: deep blue, through green and yellow, to red.
+const SCALE: [Rgb; 5] = [(20, 30, 120), (30, 130, 200), (60, 180, 75), (250, 220, 40), (220, 40, 30)];
+/// Used for the best tree, which is nowhere on the scale.
+const HIGHLIGHT: Rgb = (255, 0, 255);
+
+/// The colour for `t` from 0 to 1 along the scale, blending between neighbouring stops.
+pub fn heat(t: f64) -> Rgb {
+    let position = t.clamp(0.0, 1.0) * (SCALE.len() - 1) as f64;
+    let idx = (position.floor() as usize).min(SCALE.len() - 2);
+    let frac = position - idx as f64;
+    let blend = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * frac).round() as u8;
+    let (low, high) = (SCALE[idx], SCALE[idx + 1]);
+    (blend(low.0, high.0), blend(low.1, high.1), blend(low.2, high.2))
+}
+
+/// A grid of values from 0 to 1 to be coloured by `heat`, maybe with one tree picked out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeatMap {
+    values : Vec<Vec<f64>>,
+    highlight : Option<(usize, usize)>,
+}
+
+impl HeatMap {
+    /// Heights from 0 to 9.
+    pub fn heights(height: &[Vec<u8>]) -> Self {
+        Self::from_values(height.iter().map(|row| row.iter().map(|&h| h as f64 / 9.0).collect()).collect())
+    }
+
+    /// Visible trees at the top of the scale, hidden ones at the bottom.
+    pub fn visibility(visible: &[Vec<bool>]) -> Self {
+        Self::from_values(visible.iter().map(|row| row.iter().map(|&v| if v { 1.0 } else { 0.0 }).collect()).collect())
+    }
+
+    /// Scenic scores on a log scale, since a handful of trees dwarf everything else.
+    pub fn scenic(scores: &[Vec<u64>]) -> Self {
+        let max = scores.iter().flatten().copied().max().unwrap_or(0);
+        let top = (max as f64).ln_1p();
+        Self::from_values(scores.iter()
+            .map(|row| row.iter().map(|&s| if max == 0 { 0.0 } else { (s as f64).ln_1p() / top }).collect())
+            .collect())
+    }
+
+    fn from_values(values: Vec<Vec<f64>>) -> Self {
+        Self { values, highlight: None }
+    }
+
+    pub fn with_highlight(self, highlight: Option<(usize, usize)>) -> Self {
+        Self { highlight, ..self }
+    }
+
+    fn rows(&self) -> usize {
+        self.values.len()
+    }
+
+    fn cols(&self) -> usize {
+        self.values.first().map_or(0, |row| row.len())
+    }
+
+    fn colour(&self, r: usize, c: usize) -> Rgb {
+        if self.highlight == Some((r, c)) { HIGHLIGHT } else { heat(self.values[r][c]) }
+    }
+
+    /// RGB rows of pixels, `scale` pixels to a tree, with a ring around the highlighted tree
+    /// and the colour scale as a strip along the bottom.
+    pub fn pixels(&self, scale: usize) -> (usize, usize, Vec<u8>) {
+        let scale = scale.max(1);
+        let width = (self.cols() * scale).max(SCALE.len());
+        let strip = (2 * scale).max(4);
+        let height = self.rows() * scale + scale + strip;
+        let mut pixels = vec![0; width * height * 3];
+        let mut put = |x: usize, y: usize, (r, g, b): Rgb| {
+            let at = (y * width + x) * 3;
+            pixels[at..at + 3].copy_from_slice(&[r, g, b]);
+        };
+
+        for r in 0..self.rows() {
+            for c in 0..self.cols() {
+                let colour = self.colour(r, c);
+                for y in r * scale..(r + 1) * scale {
+                    for x in c * scale..(c + 1) * scale {
+                        put(x, y, colour);
+                    }
+                }
+            }
+        }
+        if let Some((r, c)) = self.highlight {
+            // One tree's worth of pixels around it, clipped to the map.
+            let (top, left) = ((r * scale).saturating_sub(scale), (c * scale).saturating_sub(scale));
+            let (bottom, right) = (((r + 2) * scale).min(self.rows() * scale), ((c + 2) * scale).min(self.cols() * scale));
+            for y in top..bottom {
+                for x in left..right {
+                    if y == top || y + 1 == bottom || x == left || x + 1 == right {
+                        put(x, y, HIGHLIGHT);
+                    }
+                }
+            }
+        }
+        for x in 0..width {
+            let colour = heat(x as f64 / (width - 1).max(1) as f64);
+            for y in height - strip..height {
+                put(x, y, colour);
+            }
+        }
+        (width, height, pixels)
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W, scale: usize) -> io::Result<()> {
+        let (width, height, pixels) = self.pixels(scale);
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&pixels)?;
+        Ok(())
+    }
+
+    /// Two truecolor blocks per tree so the map comes out roughly square, then the scale.
+    pub fn to_terminal(&self) -> String {
+        let block = |(r, g, b): Rgb| "██".truecolor(r, g, b).to_string();
+        let mut out = String::new();
+        for r in 0..self.rows() {
+            for c in 0..self.cols() {
+                out += &block(self.colour(r, c));
+            }
+            out.push('\n');
+        }
+        out += "low ";
+        for step in 0..=10 {
+            out += &block(heat(step as f64 / 10.0));
+        }
+        out += " high";
+        if self.highlight.is_some() {
+            out += &format!("   {} best tree", block(HIGHLIGHT));
+        }
+        out.push('\n');
+        out
+    }
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+
+    #[test]
+    fn test_heat() {
+        assert_eq!(heat(0.0), SCALE[0]);
+        assert_eq!(heat(1.0), SCALE[4]);
+        assert_eq!(heat(0.5), SCALE[2]);
+        assert_eq!(heat(-3.0), SCALE[0]);
+        assert_eq!(heat(0.125), (25, 80, 160));
+    }
+
+    #[test]
+    fn test_png() {
+        let map = HeatMap::scenic(&[vec![0, 1, 0], vec![1, 8, 1]]).with_highlight(Some((1, 1)));
+        let mut bytes = Vec::new();
+        map.write_png(&mut bytes, 4).unwrap();
+
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (12, 2 * 4 + 4 + 8));
+        let at = |x: usize, y: usize| { let i = (y * 12 + x) * 3; (pixels[i], pixels[i + 1], pixels[i + 2]) };
+        assert_eq!(at(5, 5), HIGHLIGHT);
+        assert_eq!(at(0, 4), HIGHLIGHT); // the ring
+        assert_eq!(at(1, 1), SCALE[0]);
+        assert_eq!(at(10, 1), SCALE[0]);
+        assert_eq!(at(0, 19), SCALE[0]);
+        assert_eq!(at(11, 19), SCALE[4]);
+        assert_eq!(at(0, 9), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_terminal() {
+        colored::control::set_override(false);
+        let map = HeatMap::heights(&[vec![0, 9], vec![5, 5]]).with_highlight(Some((0, 1)));
+        assert_eq!(map.to_terminal(), format!("████\n████\nlow {} high   ██ best tree\n", "██".repeat(11)));
+    }
+}
@@ -0,0 +1,90 @@
+use std::fmt;
+
+use crate::geom::{Delta, Point};
+
+/// How a knot moves to keep up with the knot in front of it.
+pub trait FollowRule: fmt::Debug {
+    /// Every cell `follower` moves through after `leader` has moved, in order, ending where it
+    /// stops. Empty if it stays put.
+    fn steps(&self, leader: Point, follower: Point) -> Vec<Point>;
+
+    /// Where `follower` ends up after `leader` has moved.
+    fn follow(&self, leader: Point, follower: Point) -> Point {
+        self.steps(leader, follower).last().copied().unwrap_or(follower)
+    }
+}
+
+/// The puzzle's rule: stay touching, diagonals included, stepping diagonally to catch up.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Chebyshev;
+
+impl FollowRule for Chebyshev {
+    fn steps(&self, leader: Point, follower: Point) -> Vec<Point> {
+        if leader.is_adjacent(&follower) {
+            vec![]
+        } else {
+            vec![follower + (leader - follower).unit_direction()]
+        }
+    }
+}
+
+/// Stay on the leader or right next to it along the grid lines, and only ever move along
+/// them too. Catching up with a leader that went diagonally can take two steps.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FourAdjacent;
+
+impl FollowRule for FourAdjacent {
+    fn steps(&self, leader: Point, mut follower: Point) -> Vec<Point> {
+        let mut steps = Vec::new();
+        while (leader - follower).manhattan() > 1 {
+            let diff = leader - follower;
+            follower += if diff.dx().abs() >= diff.dy().abs() {
+                Delta::new(diff.dx().signum(), 0)
+            } else {
+                Delta::new(0, diff.dy().signum())
+            };
+            steps.push(follower);
+        }
+        steps
+    }
+}
+
+/// Like `Chebyshev`, but the rope can go slack: the follower only moves once the leader is
+/// more than `distance` away in either direction. A distance of 1 is the puzzle's rule.
+#[derive(Debug, Clone, Copy)]
+pub struct MaxSlack {
+    pub distance : u32
+}
+
+impl FollowRule for MaxSlack {
+    fn steps(&self, leader: Point, mut follower: Point) -> Vec<Point> {
+        let mut steps = Vec::new();
+        while (leader - follower).abs().unsigned_abs() > self.distance {
+            follower += (leader - follower).unit_direction();
+            steps.push(follower);
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+
+    #[test]
+    fn test_rules() {
+        let origin = Point::new(0, 0);
+        assert_eq!(Chebyshev.follow(Point::new(1, 1), origin), origin);
+        assert_eq!(Chebyshev.follow(Point::new(2, 1), origin), Point::new(1, 1));
+
+        assert_eq!(FourAdjacent.follow(Point::new(1, 0), origin), origin);
+        assert_eq!(FourAdjacent.follow(Point::new(1, 1), origin), Point::new(1, 0));
+        assert_eq!(FourAdjacent.follow(Point::new(2, 1), origin), Point::new(2, 0));
+        assert_eq!(FourAdjacent.follow(Point::new(2, 2), origin), Point::new(2, 1));
+        assert_eq!(FourAdjacent.steps(Point::new(2, 2), origin), vec![Point::new(1, 0), Point::new(1, 1), Point::new(2, 1)]);
+
+        assert_eq!(MaxSlack { distance: 3 }.follow(Point::new(3, -2), origin), origin);
+        assert_eq!(MaxSlack { distance: 3 }.follow(Point::new(4, -2), origin), Point::new(1, -1));
+        assert_eq!(MaxSlack { distance: 0 }.follow(Point::new(2, 1), origin), Point::new(2, 1));
+    }
+}
@@ -1,7 +1,8 @@
 use std::ops::{Add, Sub, AddAssign};
 use std::cmp::max;
+use std::fmt;
 use std::str::FromStr;
-use std::num::ParseIntError;    
+use std::num::ParseIntError;
 
 /// A Cartesian Point
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -12,6 +13,14 @@ impl Point {
        Self {x, y}
    }
 
+   pub fn x(&self) -> i32 {
+       self.x
+   }
+
+   pub fn y(&self) -> i32 {
+       self.y
+   }
+
    pub fn is_adjacent(&self, other: &Point) -> bool {
         (self.x - other.x).abs() <= 1 && (self.y - other.y).abs() <= 1
     }
@@ -41,32 +50,105 @@ impl Sub for Point {
 }
 
 /// Difference between two cartesian points
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Delta { dx: i32, dy: i32 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseDeltaError {
+    /// The line wasn't `<direction> <distance>`.
+    MissingDistance(String),
+    /// The distance wasn't a whole number of steps, zero or more.
+    BadDistance(ParseIntError),
+    /// The distance was too far to fit on the grid.
+    TooFar(u32),
+    UnknownDirection(String)
+}
+
+impl fmt::Display for ParseDeltaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseDeltaError::MissingDistance(s) => write!(f, "expected '<direction> <distance>', got '{}'", s),
+            ParseDeltaError::BadDistance(e) => write!(f, "bad distance: {}", e),
+            ParseDeltaError::TooFar(distance) => write!(f, "can't move {} steps", distance),
+            ParseDeltaError::UnknownDirection(d) =>
+                write!(f, "unknown direction '{}', expected one of U, D, L, R, UL, UR, DL or DR", d),
+        }
+    }
+}
+
+impl std::error::Error for ParseDeltaError {}
+
 impl FromStr for Delta {
-    type Err = ParseIntError;
+    type Err = ParseDeltaError;
 
+    /// A motion like `R 4`, or `UL 3` for three steps diagonally up and left.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (direction, distance_str) = s.split_once(" ").unwrap();
-        let distance : i32 = distance_str.parse().unwrap();
-        match direction {
-            "U" => Ok(Delta {dx: 0, dy: distance} ),
-            "D" => Ok(Delta {dx: 0, dy: -distance} ),
-            "L" => Ok(Delta {dx: -distance, dy: 0} ),
-            "R" => Ok(Delta {dx: distance, dy: 0} ),
-            _ => unreachable!()
+        let (direction, distance_str) = s.trim().split_once(' ')
+            .ok_or_else(|| ParseDeltaError::MissingDistance(s.to_string()))?;
+        let distance : u32 = distance_str.trim().parse().map_err(ParseDeltaError::BadDistance)?;
+        let (dx, dy) = match direction {
+            "U" => (0, 1),
+            "D" => (0, -1),
+            "L" => (-1, 0),
+            "R" => (1, 0),
+            "UL" => (-1, 1),
+            "UR" => (1, 1),
+            "DL" => (-1, -1),
+            "DR" => (1, -1),
+            _ => return Err(ParseDeltaError::UnknownDirection(direction.to_string()))
+        };
+        let scale = |unit: i32| i32::try_from(distance).ok().and_then(|distance| unit.checked_mul(distance));
+        match (scale(dx), scale(dy)) {
+            (Some(dx), Some(dy)) => Ok(Delta { dx, dy }),
+            _ => Err(ParseDeltaError::TooFar(distance)),
         }
     }
 }
 
 impl Delta {
+    pub fn new(dx: i32, dy: i32) -> Self {
+        Self { dx, dy }
+    }
+
+    pub fn dx(&self) -> i32 {
+        self.dx
+    }
+
+    pub fn dy(&self) -> i32 {
+        self.dy
+    }
+
     pub fn abs(&self) -> i32 {
         // No need for Pythagorus today.
         max(self.dx.abs(), self.dy.abs())
     }
 
+    /// Distance along the grid lines, for when diagonal steps aren't allowed.
+    pub fn manhattan(&self) -> i32 {
+        self.dx.abs() + self.dy.abs()
+    }
+
     pub fn unit_direction(&self) -> Self {
         Self {dx: self.dx.signum(), dy: self.dy.signum() }
     }
 }
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("R 4".parse(), Ok(Delta::new(4, 0)));
+        assert_eq!("D 2".parse(), Ok(Delta::new(0, -2)));
+        assert_eq!("UL 3".parse(), Ok(Delta::new(-3, 3)));
+        assert_eq!("DR 1".parse::<Delta>().unwrap().unit_direction(), Delta::new(1, -1));
+        assert_eq!("X 1".parse::<Delta>(), Err(ParseDeltaError::UnknownDirection("X".to_string())));
+        assert_eq!("R".parse::<Delta>(), Err(ParseDeltaError::MissingDistance("R".to_string())));
+        assert!(matches!("R x".parse::<Delta>(), Err(ParseDeltaError::BadDistance(_))));
+        assert!(matches!("R -3".parse::<Delta>(), Err(ParseDeltaError::BadDistance(_))));
+        assert!(matches!("L -2147483648".parse::<Delta>(), Err(ParseDeltaError::BadDistance(_))));
+        assert_eq!("L 2147483647".parse(), Ok(Delta::new(-2147483647, 0)));
+        assert_eq!("L 2147483648".parse::<Delta>(), Err(ParseDeltaError::TooFar(2147483648)));
+    }
+}
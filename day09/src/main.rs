@@ -1,61 +1,245 @@
+use std::env;
 use std::fs::File;
 use std::collections::HashSet;
-use std::io::{self, prelude::*, BufReader};
+use std::io::{self, prelude::*, BufReader, BufWriter};
 
+pub mod follow;
 pub mod geom;
+pub mod playback;
 
-#[derive(Default, Debug)]
+use follow::FollowRule;
+
+#[derive(Debug)]
 pub struct RopeSimulation {
     knots: Vec<geom::Point>,
-    tail_visited: HashSet<geom::Point>
+    // Every cell each knot has been on, head first.
+    visited: Vec<HashSet<geom::Point>>,
+    // Every position of the head, oldest first.
+    head_trail: Vec<geom::Point>,
+    rule: Box<dyn FollowRule>
 }
 
 impl RopeSimulation {
     pub fn from_num_followers(followers: usize) -> Self {
-        return Self {
+        Self::with_rule(followers, Box::new(follow::Chebyshev))
+    }
+
+    pub fn with_rule(followers: usize, rule: Box<dyn FollowRule>) -> Self {
+        Self {
             knots: vec![geom::Point::new(0, 0); followers + 1],
-            tail_visited: HashSet::from([geom::Point::new(0, 0)])
+            visited: vec![HashSet::from([geom::Point::new(0, 0)]); followers + 1],
+            head_trail: vec![geom::Point::new(0, 0)],
+            rule
         }
     }
 
     pub fn step(&mut self, motion: &geom::Delta) {
+        self.step_with(motion, |_| {});
+    }
+
+    /// Like `step`, showing `on_frame` the rope after every single step of the head.
+    pub fn step_with(&mut self, motion: &geom::Delta, mut on_frame: impl FnMut(&playback::Frame)) {
         for _ in 0..motion.abs() {
             self.knots[0] += motion.unit_direction();
 
+            self.visited[0].insert(self.knots[0]);
             for i in 1..self.knots.len() {
-                if !self.knots[i-1].is_adjacent(&self.knots[i]) {
-                    let diff = self.knots[i-1] - self.knots[i];
-                    self.knots[i] += diff.unit_direction();
-                }    
+                // A knot can pass through cells on its way, which count as visited too.
+                for cell in self.rule.steps(self.knots[i-1], self.knots[i]) {
+                    self.visited[i].insert(cell);
+                    self.knots[i] = cell;
+                }
             }
+            self.head_trail.push(self.knots[0]);
 
-            self.tail_visited.insert(self.knots[self.knots.len()-1]);
+            on_frame(&playback::Frame { knots: &self.knots, head_trail: &self.head_trail, visited: &self.visited });
         }
     }
+
+    pub fn knots(&self) -> &[geom::Point] {
+        &self.knots
+    }
+
+    /// Cells visited by knot `i`, where 0 is the head.
+    pub fn visited(&self, i: usize) -> &HashSet<geom::Point> {
+        &self.visited[i]
+    }
+
+    pub fn visited_sets(&self) -> &[HashSet<geom::Point>] {
+        &self.visited
+    }
+
+    pub fn tail_visited(&self) -> &HashSet<geom::Point> {
+        &self.visited[self.visited.len()-1]
+    }
 }
 
-pub fn day09() {
+/// Read the motions from the input, stopping at the first line that doesn't make sense.
+fn read_motions() -> Result<Vec<geom::Delta>, String> {
     let file = File::open("input.txt").expect("File 'input.txt' not readable.");
+    BufReader::new(file)
+        .lines() // Get a line iterator
+        .map_while(|line| line.ok()) // Get Strings instead of Result
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| line.parse().map_err(|e| format!("line {}: {}", idx + 1, e)))
+        .collect()
+}
+
+const USAGE: &str = "usage: day09
+       day09 --rules
+       day09 --play [--knots N] [--trail N] [--every N]
+       day09 --gif FILE [--knots N] [--trail N] [--every N] [--scale N]
+       day09 --png FILE [--knots N] [--scale N]";
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
+/// Options for watching the rope move.
+struct Playback {
+    knots : usize,
+    trail : usize,
+    every : usize,
+    scale : usize
+}
+
+fn parse_playback(args: &[String]) -> Result<Playback, String> {
+    let mut playback = Playback { knots: 10, trail: 20, every: 1, scale: 4 };
+    let mut idx = 0;
+    while idx < args.len() {
+        let value = args.get(idx + 1).ok_or(format!("{} needs a value", args[idx]))?;
+        let number : usize = value.parse().map_err(|_| format!("bad number '{}' for {}", value, args[idx]))?;
+        match args[idx].as_str() {
+            "--knots" if number == 0 => return Err("a rope needs at least one knot".to_string()),
+            "--knots" => playback.knots = number,
+            "--trail" => playback.trail = number,
+            "--every" => playback.every = number,
+            "--scale" => playback.scale = number,
+            flag => return Err(format!("unexpected '{} {}'", flag, value))
+        }
+        idx += 2;
+    }
+    Ok(playback)
+}
+
+/// Show the rope in the terminal, one frame per step of the head.
+fn play(motions: &[geom::Delta], options: &Playback) {
+    let bounds = playback::Bounds::of_motions(motions);
+    let mut rope = RopeSimulation::from_num_followers(options.knots - 1);
+    let mut seen : usize = 0;
+    for motion in motions {
+        rope.step_with(motion, |frame| {
+            if seen.is_multiple_of(options.every.max(1)) {
+                println!("{}", playback::render_text(frame, &bounds, options.trail));
+            }
+            seen += 1;
+        });
+    }
+}
 
+fn write_gif(path: &str, motions: &[geom::Delta], options: &Playback) -> io::Result<()> {
+    let bounds = playback::Bounds::of_motions(motions);
+    let writer = BufWriter::new(File::create(path)?);
+    let mut recorder = playback::GifRecorder::new(writer, bounds, options.knots, options.scale, options.trail, options.every)?;
+    let mut rope = RopeSimulation::from_num_followers(options.knots - 1);
+    let mut result = Ok(());
+    for motion in motions {
+        rope.step_with(motion, |frame| if result.is_ok() { result = recorder.record(frame) });
+    }
+    result?;
+    recorder.into_inner()?.flush()
+}
+
+fn write_png(path: &str, motions: &[geom::Delta], options: &Playback) -> io::Result<()> {
+    let bounds = playback::Bounds::of_motions(motions);
+    let mut rope = RopeSimulation::from_num_followers(options.knots - 1);
+    motions.iter().for_each(|motion| rope.step(motion));
+    playback::write_visited_png(BufWriter::new(File::create(path)?), rope.visited_sets(), &bounds, options.scale)
+}
+
+pub fn day09(motions: &[geom::Delta]) {
     let mut part1 = RopeSimulation::from_num_followers(1);
     let mut part2 = RopeSimulation::from_num_followers(9);
-    let reader = BufReader::new(file)
-        .lines() // Get a line iterator
-        .filter_map(|line| line.ok()); // Get Strings instead of Result
-
-    for line in reader {
-        part1.step(&line.parse().expect("Failed to parse input line."));
-        part2.step(&line.parse().expect("Failed to parse input line."));
+    for motion in motions {
+        part1.step(motion);
+        part2.step(motion);
     }
 
-    println!("Number of spots visited in Part 1 is {}", part1.tail_visited.len());
-    println!("Number of spots visited in Part 2 is {}", part2.tail_visited.len());
+    println!("Number of spots visited in Part 1 is {}", part1.tail_visited().len());
+    println!("Number of spots visited in Part 2 is {}", part2.tail_visited().len());
+}
 
+/// Run ten-knot ropes under the other follow rules and show how far each knot got.
+fn compare_rules(motions: &[geom::Delta]) {
+    let mut slack = RopeSimulation::with_rule(9, Box::new(follow::MaxSlack { distance: 2 }));
+    let mut grid = RopeSimulation::with_rule(9, Box::new(follow::FourAdjacent));
+    for motion in motions {
+        slack.step(motion);
+        grid.step(motion);
+    }
+    for (name, rope) in [("slack of 2", &slack), ("grid-only following", &grid)] {
+        let counts : Vec<String> = (0..rope.knots().len()).map(|i| rope.visited(i).len().to_string()).collect();
+        println!("Spots visited by each knot with {}: {}", name, counts.join(", "));
+    }
 }
 
 pub fn main() -> io::Result<()> {
-    day09();
+    let motions = read_motions().unwrap_or_else(|e| fail(e));
+    let args : Vec<String> = env::args().skip(1).collect();
+    let options = |rest: &[String]| parse_playback(rest).unwrap_or_else(|e| fail(e));
+    match args.first().map(|s| s.as_str()) {
+        None => day09(&motions),
+        Some("--rules") if args.len() == 1 => compare_rules(&motions),
+        Some("--play") => play(&motions, &options(&args[1..])),
+        Some("--gif") if args.len() > 1 => write_gif(&args[1], &motions, &options(&args[2..]))?,
+        Some("--png") if args.len() > 1 => write_png(&args[1], &motions, &options(&args[2..]))?,
+        Some(arg) => fail(format!("unexpected '{}'", arg))
+    }
     Ok(())
 }
 //6030
 //2545
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+
+    fn simulate(mut rope: RopeSimulation, motions: &str) -> RopeSimulation {
+        for line in motions.lines() {
+            rope.step(&line.parse().unwrap());
+        }
+        rope
+    }
+
+    const SAMPLE: &str = "R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2";
+    const LARGER_SAMPLE: &str = "R 5\nU 8\nL 8\nD 3\nR 17\nD 10\nL 25\nU 20";
+
+    #[test]
+    fn test_samples() {
+        let rope = simulate(RopeSimulation::from_num_followers(1), SAMPLE);
+        assert_eq!(rope.tail_visited().len(), 13);
+        let rope = simulate(RopeSimulation::from_num_followers(9), SAMPLE);
+        assert_eq!(rope.tail_visited().len(), 1);
+        let rope = simulate(RopeSimulation::from_num_followers(9), LARGER_SAMPLE);
+        assert_eq!(rope.tail_visited().len(), 36);
+        // The second knot follows the same path as a two-knot rope's tail.
+        let short = simulate(RopeSimulation::from_num_followers(1), LARGER_SAMPLE);
+        assert_eq!(rope.visited(1), short.tail_visited());
+        assert_eq!(rope.visited(1).len(), 88);
+    }
+
+    #[test]
+    fn test_rules() {
+        let slack = simulate(RopeSimulation::with_rule(1, Box::new(follow::MaxSlack { distance: 1 })), SAMPLE);
+        assert_eq!(slack.tail_visited().len(), 13);
+        let slack = simulate(RopeSimulation::with_rule(1, Box::new(follow::MaxSlack { distance: 2 })), "R 4\nUL 2");
+        assert_eq!(slack.knots(), [geom::Point::new(2, 2), geom::Point::new(2, 0)]);
+
+        let grid = simulate(RopeSimulation::with_rule(2, Box::new(follow::FourAdjacent)), "UR 2");
+        assert_eq!(grid.knots(), [geom::Point::new(2, 2), geom::Point::new(2, 1), geom::Point::new(2, 0)]);
+        assert_eq!(grid.visited(1), &HashSet::from([geom::Point::new(0, 0), geom::Point::new(1, 0), geom::Point::new(1, 1), geom::Point::new(2, 1)]));
+    }
+}
@@ -0,0 +1,304 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use crate::geom::{Delta, Point};
+
+/// What the rope looks like after one unit step of the head.
+#[derive(Debug, Clone, Copy)]
+pub struct Frame<'a> {
+    /// Head first.
+    pub knots : &'a [Point],
+    /// Everywhere the head has been, oldest first, ending where it is now.
+    pub head_trail : &'a [Point],
+    /// Every cell each knot has been on so far, head first.
+    pub visited : &'a [HashSet<Point>],
+}
+
+/// The smallest box holding every cell, inclusive at both ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub min_x : i32,
+    pub max_x : i32,
+    pub min_y : i32,
+    pub max_y : i32,
+}
+
+impl Bounds {
+    /// Everywhere the head goes while following `motions` from the origin. Followers only ever
+    /// step towards the knot in front, so they never leave the box either.
+    pub fn of_motions<'a>(motions: impl IntoIterator<Item = &'a Delta>) -> Self {
+        let mut head = Point::new(0, 0);
+        let mut bounds = Self { min_x: 0, max_x: 0, min_y: 0, max_y: 0 };
+        for motion in motions {
+            head += *motion;
+            bounds.min_x = bounds.min_x.min(head.x());
+            bounds.max_x = bounds.max_x.max(head.x());
+            bounds.min_y = bounds.min_y.min(head.y());
+            bounds.max_y = bounds.max_y.max(head.y());
+        }
+        bounds
+    }
+
+    pub fn width(&self) -> usize {
+        (self.max_x - self.min_x + 1) as usize
+    }
+
+    pub fn height(&self) -> usize {
+        (self.max_y - self.min_y + 1) as usize
+    }
+
+    /// Row-major index of a cell, with the top row (largest y) first.
+    fn index(&self, point: Point) -> Option<usize> {
+        let inside = (self.min_x..=self.max_x).contains(&point.x()) && (self.min_y..=self.max_y).contains(&point.y());
+        inside.then(|| (self.max_y - point.y()) as usize * self.width() + (point.x() - self.min_x) as usize)
+    }
+}
+
+/// What's drawn in each cell of a frame, also used as its palette index in GIFs.
+const EMPTY: u8 = 0;
+const TAIL_VISITED: u8 = 1;
+const HEAD_TRAIL: u8 = 2;
+const START: u8 = 3;
+const FIRST_KNOT: u8 = 4;
+const MAX_KNOT_COLOURS: usize = 256 - FIRST_KNOT as usize;
+
+/// One palette index per cell: cells the tail has visited, the last `trail` cells the head
+/// was on, the start, and the knots on top with the head above all.
+fn frame_cells(frame: &Frame, bounds: &Bounds, trail: usize) -> Vec<u8> {
+    let mut cells = vec![EMPTY; bounds.width() * bounds.height()];
+    let mut paint = |point: Point, value: u8| {
+        if let Some(idx) = bounds.index(point) { cells[idx] = value; }
+    };
+    for &point in frame.visited.last().into_iter().flatten() {
+        paint(point, TAIL_VISITED);
+    }
+    for &point in &frame.head_trail[frame.head_trail.len().saturating_sub(trail)..] {
+        paint(point, HEAD_TRAIL);
+    }
+    paint(Point::new(0, 0), START);
+    for (i, &knot) in frame.knots.iter().enumerate().rev() {
+        paint(knot, FIRST_KNOT + i.min(MAX_KNOT_COLOURS - 1) as u8);
+    }
+    cells
+}
+
+/// A frame as text like the puzzle's diagrams: `H` and digits for knots, `s` for the start,
+/// `#` where the tail has been and `~` for the head's recent trail.
+pub fn render_text(frame: &Frame, bounds: &Bounds, trail: usize) -> String {
+    let cells = frame_cells(frame, bounds, trail);
+    let mut out = String::with_capacity(cells.len() + bounds.height());
+    for row in cells.chunks(bounds.width()) {
+        out.extend(row.iter().map(|&cell| match cell {
+            EMPTY => '.',
+            TAIL_VISITED => '#',
+            HEAD_TRAIL => '~',
+            START => 's',
+            FIRST_KNOT => 'H',
+            knot => std::char::from_digit((knot - FIRST_KNOT) as u32, 36).unwrap_or('*'),
+        }));
+        out.push('\n');
+    }
+    out
+}
+
+pub type Rgb = (u8, u8, u8);
+
+/// Knots go round the colour wheel from red at the head to violet at the tail.
+pub fn knot_colour(i: usize, num_knots: usize) -> Rgb {
+    let hue = 300.0 * i as f64 / (num_knots.max(2) - 1) as f64;
+    let sector = (hue / 60.0).floor() as usize;
+    let rising = ((hue / 60.0 - sector as f64) * 255.0).round() as u8;
+    let falling = 255 - rising;
+    match sector {
+        0 => (255, rising, 0),
+        1 => (falling, 255, 0),
+        2 => (0, 255, rising),
+        3 => (0, falling, 255),
+        4 => (rising, 0, 255),
+        _ => (255, 0, 255),
+    }
+}
+
+fn palette(num_knots: usize) -> Vec<u8> {
+    let mut colours : Vec<Rgb> = vec![(16, 16, 24), (90, 90, 110), (70, 110, 70), (255, 255, 255)];
+    colours.extend((0..num_knots.min(MAX_KNOT_COLOURS)).map(|i| knot_colour(i, num_knots)));
+    colours.into_iter().flat_map(|(r, g, b)| [r, g, b]).collect()
+}
+
+/// Blow each cell up into a `scale` by `scale` square of pixels.
+fn upscale<T: Copy>(cells: &[T], width: usize, scale: usize) -> Vec<T> {
+    cells.chunks(width)
+        .flat_map(|row| {
+            let wide : Vec<T> = row.iter().flat_map(|&cell| std::iter::repeat_n(cell, scale)).collect();
+            std::iter::repeat_n(wide, scale).flatten()
+        })
+        .collect()
+}
+
+/// Writes frames into an animated GIF, keeping every `every`th one.
+pub struct GifRecorder<W: Write> {
+    encoder : gif::Encoder<W>,
+    bounds : Bounds,
+    scale : usize,
+    trail : usize,
+    every : usize,
+    seen : usize,
+}
+
+fn gif_error(e: gif::EncodingError) -> io::Error {
+    match e {
+        gif::EncodingError::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidInput, e),
+    }
+}
+
+impl<W: Write> GifRecorder<W> {
+    pub fn new(writer: W, bounds: Bounds, num_knots: usize, scale: usize, trail: usize, every: usize) -> io::Result<Self> {
+        let scale = scale.max(1);
+        let (width, height) = (bounds.width() * scale, bounds.height() * scale);
+        if width > u16::MAX as usize || height > u16::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{}x{} is too big for a GIF", width, height)));
+        }
+        let mut encoder = gif::Encoder::new(writer, width as u16, height as u16, &palette(num_knots)).map_err(gif_error)?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(gif_error)?;
+        Ok(Self { encoder, bounds, scale, trail, every: every.max(1), seen: 0 })
+    }
+
+    pub fn record(&mut self, frame: &Frame) -> io::Result<()> {
+        self.seen += 1;
+        if !(self.seen - 1).is_multiple_of(self.every) {
+            return Ok(());
+        }
+        let cells = frame_cells(frame, &self.bounds, self.trail);
+        let image = gif::Frame {
+            width: (self.bounds.width() * self.scale) as u16,
+            height: (self.bounds.height() * self.scale) as u16,
+            buffer: Cow::Owned(upscale(&cells, self.bounds.width(), self.scale)),
+            delay: 4,
+            ..gif::Frame::default()
+        };
+        self.encoder.write_frame(&image).map_err(gif_error)
+    }
+
+    pub fn into_inner(self) -> io::Result<W> {
+        self.encoder.into_inner()
+    }
+}
+
+/// A picture of every cell each knot visited. The head goes down first and each knot after
+/// it is painted on top, so the tail's path shows up inside the bands of the knots before it.
+pub fn write_visited_png<W: Write>(writer: W, visited: &[HashSet<Point>], bounds: &Bounds, scale: usize) -> io::Result<()> {
+    let scale = scale.max(1);
+    let mut cells = vec![(16, 16, 24); bounds.width() * bounds.height()];
+    for (i, knot_visited) in visited.iter().enumerate() {
+        let colour = knot_colour(i, visited.len());
+        for &point in knot_visited {
+            if let Some(idx) = bounds.index(point) { cells[idx] = colour; }
+        }
+    }
+    let pixels : Vec<u8> = upscale(&cells, bounds.width(), scale).into_iter().flat_map(|(r, g, b)| [r, g, b]).collect();
+
+    let mut encoder = png::Encoder::new(writer, (bounds.width() * scale) as u32, (bounds.height() * scale) as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&pixels)?;
+    Ok(())
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+    use crate::RopeSimulation;
+
+    const SAMPLE: &str = "R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2";
+
+    fn motions(text: &str) -> Vec<Delta> {
+        text.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_bounds() {
+        let bounds = Bounds::of_motions(&motions(SAMPLE));
+        assert_eq!(bounds, Bounds { min_x: 0, max_x: 5, min_y: 0, max_y: 4 });
+        assert_eq!(Bounds::of_motions(&motions("L 2\nDL 3")), Bounds { min_x: -5, max_x: 0, min_y: -3, max_y: 0 });
+    }
+
+    #[test]
+    fn test_text_frames() {
+        let motions = motions(SAMPLE);
+        let bounds = Bounds::of_motions(&motions);
+        let mut rope = RopeSimulation::from_num_followers(9);
+        let mut frames = Vec::new();
+        for motion in &motions {
+            rope.step_with(motion, |frame| frames.push(render_text(frame, &bounds, 0)));
+        }
+        assert_eq!(frames.len(), 24);
+        // After the first `U 4`, as drawn in the puzzle.
+        assert_eq!(frames[7], "\
+....H.
+....1.
+..432.
+.5....
+6.....
+");
+        assert_eq!(frames.last().unwrap(), "\
+......
+......
+.1H3..
+.5....
+6.....
+");
+    }
+
+    #[test]
+    fn test_trail_and_gif() {
+        let motions = motions("R 3\nU 1");
+        let bounds = Bounds::of_motions(&motions);
+        let mut rope = RopeSimulation::from_num_followers(1);
+        let mut recorder = GifRecorder::new(Vec::new(), bounds, 2, 3, 2, 1).unwrap();
+        let mut last = String::new();
+        for motion in &motions {
+            rope.step_with(motion, |frame| {
+                recorder.record(frame).unwrap();
+                last = render_text(frame, &bounds, 3);
+            });
+        }
+        assert_eq!(last, "...H\ns#1~\n");
+
+        let bytes = recorder.into_inner().unwrap();
+        let mut decoder = gif::DecodeOptions::new().read_info(bytes.as_slice()).unwrap();
+        let mut count = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!((frame.width, frame.height), (12, 6));
+            count += 1;
+        }
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn test_visited_png() {
+        let motions = motions(SAMPLE);
+        let bounds = Bounds::of_motions(&motions);
+        let mut rope = RopeSimulation::from_num_followers(1);
+        motions.iter().for_each(|motion| rope.step(motion));
+
+        let mut bytes = Vec::new();
+        write_visited_png(&mut bytes, rope.visited_sets(), &bounds, 2).unwrap();
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (12, 10));
+        let at = |x: usize, y: usize| { let i = (y * 12 + x) * 3; (pixels[i], pixels[i + 1], pixels[i + 2]) };
+        assert_eq!(at(0, 9), knot_colour(1, 2)); // the start, bottom left
+        assert_eq!(at(11, 0), (16, 16, 24)); // nobody went to the top right
+        assert_eq!(at(8, 0), knot_colour(0, 2)); // only the head made it to the top
+    }
+
+    #[test]
+    fn test_knot_colours() {
+        assert_eq!(knot_colour(0, 10), (255, 0, 0));
+        assert_eq!(knot_colour(9, 10), (255, 0, 255));
+        assert_eq!(palette(10).len(), 3 * 14);
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::Index;
+
+use crate::elf::Instruction;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    UnknownMnemonic(String),
+    WrongOperandCount { mnemonic: String, expected: usize, found: usize },
+    BadRegister(String),
+    BadValue(String),
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    /// A jump to an instruction number past the end of the program.
+    BadTarget(usize)
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::UnknownMnemonic(m) => write!(f, "unknown instruction '{}'", m),
+            ErrorKind::WrongOperandCount { mnemonic, expected, found } =>
+                write!(f, "'{}' takes {} operand(s), not {}", mnemonic, expected, found),
+            ErrorKind::BadRegister(r) => write!(f, "no such register '{}'", r),
+            ErrorKind::BadValue(v) => write!(f, "'{}' isn't a number that fits in a register", v),
+            ErrorKind::UndefinedLabel(l) => write!(f, "label '{}' is never defined", l),
+            ErrorKind::DuplicateLabel(l) => write!(f, "label '{}' is defined more than once", l),
+            ErrorKind::BadTarget(t) => write!(f, "there's no instruction {} to jump to", t),
+        }
+    }
+}
+
+/// Something wrong with one line of a program, numbered from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub line : usize,
+    pub kind : ErrorKind
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Assembled instructions, remembering the names of the labels for the disassembler.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program {
+    instructions : Vec<Instruction>,
+    /// The first name given to each labelled instruction.
+    labels : BTreeMap<usize, String>
+}
+
+impl Program {
+    pub fn new(instructions: Vec<Instruction>) -> Self {
+        Self { instructions, labels: BTreeMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn label(&self, idx: usize) -> Option<&str> {
+        self.labels.get(&idx).map(|s| s.as_str())
+    }
+}
+
+impl Index<usize> for Program {
+    type Output = Instruction;
+
+    fn index(&self, idx: usize) -> &Instruction {
+        &self.instructions[idx]
+    }
+}
+
+/// Split a line into its mnemonic and operands, dropping any `;` comment. Operands can be
+/// separated by commas, spaces or both.
+pub fn split_instruction(line: &str) -> (&str, Vec<&str>) {
+    let code = line.split(';').next().unwrap_or("").trim();
+    let (mnemonic, rest) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+    let operands = rest.split(|c: char| c == ',' || c.is_whitespace()).filter(|s| !s.is_empty()).collect();
+    (mnemonic, operands)
+}
+
+/// Split a `name:` label off the front of a line, if there is one.
+fn split_label(line: &str) -> (Option<&str>, &str) {
+    let code = line.split(';').next().unwrap_or("");
+    match code.split_once(':') {
+        Some((label, rest)) if !label.trim().is_empty() && !label.trim().contains(char::is_whitespace) =>
+            (Some(label.trim()), rest),
+        _ => (None, code),
+    }
+}
+
+/// Assemble a program, reporting every line that's wrong rather than only the first.
+///
+/// Each line holds at most one instruction, maybe after a `name:` label. Jumps can go to a
+/// label or to an instruction number.
+pub fn assemble(source: &str) -> Result<Program, Vec<Error>> {
+    let mut errors = Vec::new();
+    let mut label_targets : HashMap<&str, usize> = HashMap::new();
+    let mut labels = BTreeMap::new();
+    let mut lines = Vec::new();
+
+    // Find where every label points before reading any jumps.
+    for (idx, line) in source.lines().enumerate() {
+        let (label, code) = split_label(line);
+        if let Some(label) = label {
+            if label_targets.insert(label, lines.len()).is_some() {
+                errors.push(Error { line: idx + 1, kind: ErrorKind::DuplicateLabel(label.to_string()) });
+            }
+            labels.entry(lines.len()).or_insert_with(|| label.to_string());
+        }
+        let (mnemonic, operands) = split_instruction(code);
+        if !mnemonic.is_empty() {
+            lines.push((idx + 1, mnemonic, operands));
+        }
+    }
+
+    let num_instructions = lines.len();
+    let mut instructions = Vec::with_capacity(num_instructions);
+    for (line, mnemonic, operands) in lines {
+        let target = |name: &str| match (label_targets.get(name), name.parse::<usize>()) {
+            (Some(&target), _) => Ok(target),
+            (None, Ok(target)) if target <= num_instructions => Ok(target),
+            (None, Ok(target)) => Err(ErrorKind::BadTarget(target)),
+            (None, Err(_)) => Err(ErrorKind::UndefinedLabel(name.to_string())),
+        };
+        match Instruction::parse_with(mnemonic, &operands, target) {
+            Ok(instruction) => instructions.push(instruction),
+            Err(kind) => errors.push(Error { line, kind }),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(Program { instructions, labels })
+    } else {
+        errors.sort_by_key(|e| e.line);
+        Err(errors)
+    }
+}
+
+/// Print a program back out as source that assembles to the same thing. Jump targets that
+/// never had a label get made-up ones.
+pub fn disassemble(program: &Program) -> String {
+    let mut names = program.labels.clone();
+    for instruction in &program.instructions {
+        if let Instruction::Jump { target, .. } = *instruction {
+            if !names.contains_key(&target) {
+                let mut name = format!("L{}", target);
+                while names.values().any(|n| *n == name) { name.push('_'); }
+                names.insert(target, name);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for idx in 0..=program.len() {
+        if let Some(name) = names.get(&idx) {
+            out += &format!("{}:\n", name);
+        }
+        if let Some(instruction) = program.instructions.get(idx) {
+            out += "    ";
+            instruction.write_with(&mut out, |target| names[&target].clone()).unwrap();
+            out.push('\n');
+        }
+    }
+    out
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", disassemble(self))
+    }
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+    use crate::elf::{Condition, Cpu, Fault, Operand, Register};
+
+    const FACTORIAL: &str = "
+; 5! into b, the slow way
+        set a, 5
+        set b, 1
+loop:   jz a, done
+        mul b, a        ; 4 cycles
+        add a, -1
+        jmp loop
+done:   halt
+        noop            ; never reached
+";
+
+    #[test]
+    fn test_assemble() {
+        let program = assemble(FACTORIAL).unwrap();
+        assert_eq!(program.len(), 8);
+        assert_eq!(program[2], Instruction::Jump { condition: Condition::Zero(Register::A), target: 6 });
+        assert_eq!(program[3], Instruction::Mul(Register::B, Operand::Register(Register::A)));
+        assert_eq!(program[4], Instruction::Add(Register::A, Operand::Value(-1)));
+        assert_eq!(program.label(6), Some("done"));
+
+        let mut cpu = Cpu::with_device(());
+        cpu.run(&program, 100).unwrap();
+        assert_eq!(cpu.register(Register::B), 120);
+        assert!(cpu.halted);
+        assert_eq!(cpu.pc, 7);
+        // 1 + 1, then five trips round the loop at 2 + 4 + 2 + 2, then the last jz and halt.
+        assert_eq!(cpu.cycle - 1, 2 + 5 * 10 + 2 + 1);
+    }
+
+    #[test]
+    fn test_overflow() {
+        // Every kind of arithmetic stops the CPU rather than wrapping.
+        for (source, lhs, rhs) in [
+            ("addx 2147483647", 1, i32::MAX),
+            ("set a, -2147483648\nadd a, -1", i32::MIN, -1),
+            ("set b, 65536\nmul b, b", 65536, 65536),
+        ] {
+            let program = assemble(source).unwrap();
+            let mut cpu = Cpu::with_device(());
+            let Err(Fault::Overflow(overflow)) = cpu.run(&program, 100) else { panic!("no overflow") };
+            assert_eq!((overflow.pc, overflow.instruction, overflow.lhs, overflow.rhs), (program.len() - 1, program[program.len() - 1], lhs, rhs));
+            assert!(cpu.halted);
+            assert_eq!(cpu.pc, program.len() - 1);
+            assert_eq!(cpu.run(&program, 100), Ok(()));
+        }
+        let program = assemble("mul x, 1000000\nmul x, x").unwrap();
+        assert_eq!(Cpu::with_device(()).run(&program, 100).unwrap_err().to_string(),
+            "instruction 1 (mul x, x) overflowed working with 1000000 and 1000000");
+    }
+
+    #[test]
+    fn test_runaway() {
+        let program = assemble("top: jmp top").unwrap();
+        let mut cpu = Cpu::with_device(());
+        assert_eq!(cpu.run(&program, 1000), Err(Fault::CycleLimit(1000)));
+        assert_eq!(cpu.cycle, 1001);
+        assert!(cpu.halted);
+
+        // The clock stops rather than wrapping round to cycle 0.
+        let mut cpu = Cpu::with_device(());
+        cpu.cycle = u32::MAX;
+        assert_eq!(cpu.tick(&program), Err(Fault::ClockOverflow));
+        assert_eq!(cpu.cycle, u32::MAX);
+        assert_eq!(cpu.tick(&program), Ok(false));
+    }
+
+    #[test]
+    fn test_errors() {
+        let source = "
+start:
+    addx
+    mul q, 3
+    frob 1
+start: jnz x, nowhere
+    add a, 12x
+    jmp 99
+    noop";
+        let errors = assemble(source).unwrap_err();
+        let kinds : Vec<(usize, ErrorKind)> = errors.into_iter().map(|e| (e.line, e.kind)).collect();
+        assert_eq!(kinds, vec![
+            (3, ErrorKind::WrongOperandCount { mnemonic: "addx".to_string(), expected: 1, found: 0 }),
+            (4, ErrorKind::BadRegister("q".to_string())),
+            (5, ErrorKind::UnknownMnemonic("frob".to_string())),
+            (6, ErrorKind::DuplicateLabel("start".to_string())),
+            (6, ErrorKind::UndefinedLabel("nowhere".to_string())),
+            (7, ErrorKind::BadValue("12x".to_string())),
+            (8, ErrorKind::BadTarget(99)),
+        ]);
+        assert_eq!(assemble("noop\njmp 2").map(|p| p.len()), Ok(2));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let program = assemble(FACTORIAL).unwrap();
+        let text = disassemble(&program);
+        assert_eq!(text, "    set a, 5
+    set b, 1
+loop:
+    jz a, done
+    mul b, a
+    add a, -1
+    jmp loop
+done:
+    halt
+    noop
+");
+        assert_eq!(assemble(&text), Ok(program));
+
+        let unlabelled = Program::new(vec![Instruction::AddX(3), Instruction::Jump { condition: Condition::Positive(Register::X), target: 0 }]);
+        assert_eq!(unlabelled.to_string(), "L0:\n    addx 3\n    jpos x, L0\n");
+        assert_eq!(assemble(&unlabelled.to_string()).unwrap().instructions(), unlabelled.instructions());
+    }
+}
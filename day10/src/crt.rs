@@ -0,0 +1,161 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::fs::File;
+use colored::*;
+
+pub const SCREEN_WIDTH : usize = 40;
+pub const SCREEN_HEIGHT : usize = 6;
+
+/// Which pixels are lit, top row first.
+pub type Framebuffer = [[bool; SCREEN_WIDTH]; SCREEN_HEIGHT];
+
+/// Something wired up to the CPU that sees every cycle.
+pub trait Device {
+    /// Called during cycle `cycle` (counting from 1), with X as it is during that cycle.
+    fn tick(&mut self, cycle: u32, register_x: i32);
+}
+
+/// A CPU with nothing attached.
+impl Device for () {
+    fn tick(&mut self, _cycle: u32, _register_x: i32) { }
+}
+
+/// The CRT: the beam draws one pixel a cycle, lit if it lands on the three pixel wide sprite
+/// centred on X. It starts again at the top left every 240 cycles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crt {
+    framebuffer : Framebuffer
+}
+
+impl Default for Crt {
+    fn default() -> Self {
+        Self { framebuffer: [[false; SCREEN_WIDTH]; SCREEN_HEIGHT] }
+    }
+}
+
+impl Crt {
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+}
+
+impl Device for Crt {
+    fn tick(&mut self, cycle: u32, register_x: i32) {
+        let beam = (cycle as usize - 1) % (SCREEN_WIDTH * SCREEN_HEIGHT);
+        let (row, col) = (beam / SCREEN_WIDTH, beam % SCREEN_WIDTH);
+        self.framebuffer[row][col] = (register_x - col as i32).abs() <= 1;
+    }
+}
+
+/// The screen as `#` and `.`, one line per row like the puzzle draws it.
+pub fn to_text(framebuffer: &Framebuffer) -> String {
+    framebuffer.iter()
+        .map(|row| row.iter().map(|&lit| if lit { '#' } else { '.' }).collect::<String>() + "\n")
+        .collect()
+}
+
+/// Somewhere to show what's in the framebuffer.
+pub trait Screen {
+    fn show(&mut self, framebuffer: &Framebuffer) -> io::Result<()>;
+}
+
+/// Shows nothing, for running headless.
+#[derive(Debug, Default)]
+pub struct NullScreen;
+
+impl Screen for NullScreen {
+    fn show(&mut self, _framebuffer: &Framebuffer) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The puzzle's `#` and `.`, for when colours would get in the way.
+#[derive(Debug)]
+pub struct TextScreen<W: Write> {
+    writer : W
+}
+
+impl<W: Write> TextScreen<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> Screen for TextScreen<W> {
+    fn show(&mut self, framebuffer: &Framebuffer) -> io::Result<()> {
+        write!(self.writer, "{}", to_text(framebuffer))?;
+        self.writer.flush()
+    }
+}
+
+/// Block characters between two rules, in a different colour for each letter.
+#[derive(Debug)]
+pub struct TerminalScreen<W: Write> {
+    writer : W
+}
+
+impl<W: Write> TerminalScreen<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> Screen for TerminalScreen<W> {
+    fn show(&mut self, framebuffer: &Framebuffer) -> io::Result<()> {
+        // Use one color per character because we cool that way.
+        const RAINBOW_PLUS_WHITE : [colored::Color; 8] = [
+            Color::TrueColor {r: 255, g: 0, b: 0},
+            Color::TrueColor {r: 255, g: 127, b: 0},
+            Color::TrueColor {r: 255, g: 255, b: 0},
+            Color::TrueColor {r: 0, g: 255, b: 0},
+            Color::TrueColor {r: 0, g: 0, b: 255},
+            Color::TrueColor {r: 75, g: 0, b: 130},
+            Color::TrueColor {r: 148, g: 0, b: 211},
+            Color::TrueColor {r: 255, g: 255, b: 255},
+        ];
+
+        writeln!(self.writer, "{}", "-".repeat(SCREEN_WIDTH))?;
+        for row in framebuffer {
+            for (col, &lit) in row.iter().enumerate() {
+                if lit {
+                    write!(self.writer, "{}", "█".color(RAINBOW_PLUS_WHITE[col / ocr::CHAR_WIDTH]))?;
+                } else {
+                    write!(self.writer, " ")?;
+                }
+            }
+            writeln!(self.writer)?;
+        }
+        writeln!(self.writer, "{}", "-".repeat(SCREEN_WIDTH))?;
+        self.writer.flush()
+    }
+}
+
+/// Writes the screen to a PNG, `scale` pixels to a screen pixel, light on dark.
+#[derive(Debug)]
+pub struct PngScreen {
+    pub path : PathBuf,
+    pub scale : usize
+}
+
+impl PngScreen {
+    pub fn encode<W: Write>(framebuffer: &Framebuffer, writer: W, scale: usize) -> io::Result<()> {
+        let scale = scale.max(1);
+        let (width, height) = (SCREEN_WIDTH * scale, SCREEN_HEIGHT * scale);
+        let pixels : Vec<u8> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| framebuffer[y / scale][x / scale]))
+            .map(|lit| if lit { 0xe0 } else { 0x10 })
+            .collect();
+
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&pixels)?;
+        Ok(())
+    }
+}
+
+impl Screen for PngScreen {
+    fn show(&mut self, framebuffer: &Framebuffer) -> io::Result<()> {
+        Self::encode(framebuffer, io::BufWriter::new(File::create(&self.path)?), self.scale)
+    }
+}
@@ -0,0 +1,449 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+
+use crate::asm::Program;
+use crate::crt;
+use crate::elf::{Cpu, Register};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge
+}
+
+impl Comparison {
+    const ALL : [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Eq), ("!=", Comparison::Ne), ("<=", Comparison::Le),
+        (">=", Comparison::Ge), ("<", Comparison::Lt), (">", Comparison::Gt),
+    ];
+
+    fn holds(&self, left: i32, right: i32) -> bool {
+        match self {
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        Comparison::ALL.iter().find(|(_, c)| c == self).unwrap().0
+    }
+}
+
+/// Where `continue` should stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Just before cycle `n` starts.
+    Cycle(u32),
+    /// Just before the instruction at `n` starts.
+    Pc(usize),
+    /// When a register starts comparing true against a value.
+    Register { register: Register, comparison: Comparison, value: i32 }
+}
+
+impl Breakpoint {
+    /// Whether to stop at `cpu`, which was `before` a cycle ago.
+    fn hit(&self, before: &Cpu, cpu: &Cpu) -> bool {
+        match *self {
+            Breakpoint::Cycle(cycle) => cpu.cycle == cycle,
+            Breakpoint::Pc(pc) => cpu.current.is_none() && cpu.pc == pc,
+            Breakpoint::Register { register, comparison, value } =>
+                comparison.holds(cpu.register(register), value) && !comparison.holds(before.register(register), value),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Cycle(cycle) => write!(f, "cycle {}", cycle),
+            Breakpoint::Pc(pc) => write!(f, "pc {}", pc),
+            Breakpoint::Register { register, comparison, value } => write!(f, "{} {} {}", register, comparison.symbol(), value),
+        }
+    }
+}
+
+const HELP: &str = "\
+step [N]         run N instructions (default 1)
+tick [N]         run N cycles (default 1)
+continue         run until a breakpoint or the end
+back [N]         undo N cycles, as far back as the last 10000
+rstep [N]        undo N instructions
+break cycle N    stop before cycle N
+break pc N|LABEL stop before the instruction at N or LABEL
+break R OP N     stop when register R compares true, OP is one of == != < <= > >=
+breaks           list breakpoints
+delete N         remove breakpoint N
+regs             show every register
+print R          show one register
+where            show the cycle, program counter and instruction
+screen           show the CRT
+trace FILE|off   write every cycle to FILE from now on
+quit             stop debugging";
+
+/// A command that couldn't be run: either it made no sense, or output failed.
+enum CommandError {
+    Usage(String),
+    Io(io::Error)
+}
+
+impl From<io::Error> for CommandError {
+    fn from(e: io::Error) -> Self {
+        CommandError::Io(e)
+    }
+}
+
+/// How many snapshots the debugger keeps unless told otherwise.
+pub const HISTORY_LIMIT : usize = 10_000;
+
+/// Runs a program a cycle at a time under the control of commands, keeping a snapshot of
+/// the CPU from before each of the last few cycles so it can run backwards too.
+pub struct Debugger<'p> {
+    program : &'p Program,
+    cpu : Cpu,
+    history : VecDeque<Cpu>,
+    history_limit : usize,
+    /// Whether older snapshots have been dropped to make room.
+    forgotten : bool,
+    breakpoints : Vec<Breakpoint>,
+    trace : Option<Box<dyn Write>>
+}
+
+impl<'p> Debugger<'p> {
+    pub fn new(program: &'p Program) -> Self {
+        Self {
+            program,
+            cpu: Cpu::new(),
+            history: VecDeque::new(),
+            history_limit: HISTORY_LIMIT,
+            forgotten: false,
+            breakpoints: Vec::new(),
+            trace: None
+        }
+    }
+
+    /// Write a line for every cycle run from now on to `writer`.
+    pub fn set_trace(&mut self, writer: Option<Box<dyn Write>>) {
+        self.trace = writer;
+    }
+
+    /// Run one cycle, if there's anything left to run, saying so if it goes wrong.
+    fn tick(&mut self, out: &mut impl Write) -> io::Result<bool> {
+        if self.cpu.is_finished(self.program) {
+            return Ok(false);
+        }
+        if let Some(trace) = self.trace.as_mut() {
+            writeln!(trace, "{}", status(self.program, &self.cpu))?;
+        }
+        if self.history.len() == self.history_limit {
+            self.history.pop_front();
+            self.forgotten = true;
+        }
+        self.history.push_back(self.cpu.clone());
+        if let Err(fault) = self.cpu.tick(self.program) {
+            writeln!(out, "{}", fault)?;
+        }
+        Ok(true)
+    }
+
+    /// Run at least one cycle, then carry on until `done` says so, a breakpoint is hit or the
+    /// program finishes. Returns true if it stopped early, saying why.
+    fn run_until(&mut self, out: &mut impl Write, mut done: impl FnMut(&Cpu) -> bool) -> io::Result<bool> {
+        self.tick(out)?;
+        while !done(&self.cpu) {
+            let before = self.history.back().expect("we've run at least one cycle");
+            if let Some((idx, breakpoint)) = self.breakpoints.iter().enumerate().find(|(_, b)| b.hit(before, &self.cpu)) {
+                writeln!(out, "breakpoint {} ({}) hit", idx + 1, breakpoint)?;
+                return Ok(true);
+            }
+            if !self.tick(out)? {
+                writeln!(out, "the program has finished")?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn parse_breakpoint(&self, args: &[&str]) -> Result<Breakpoint, String> {
+        match args {
+            ["cycle", n] => n.parse().map(Breakpoint::Cycle).map_err(|_| format!("bad cycle '{}'", n)),
+            ["pc", target] => target.parse().ok()
+                .or_else(|| (0..=self.program.len()).find(|&idx| self.program.label(idx) == Some(*target)))
+                .map(Breakpoint::Pc)
+                .ok_or(format!("no instruction or label '{}'", target)),
+            [register, op, value] => {
+                let register = register.parse().map_err(|e| format!("{}", e))?;
+                let comparison = Comparison::ALL.iter().find(|(symbol, _)| symbol == op)
+                    .ok_or(format!("unknown comparison '{}'", op))?.1;
+                let value = value.parse().map_err(|_| format!("bad value '{}'", value))?;
+                Ok(Breakpoint::Register { register, comparison, value })
+            },
+            _ => Err("usage: break cycle N | break pc N | break R OP N".to_string())
+        }
+    }
+
+    /// Run `n` instructions or cycles, stopping early at a breakpoint or the end.
+    fn advance(&mut self, n: usize, by_instruction: bool, out: &mut impl Write) -> io::Result<()> {
+        for _ in 0..n {
+            if self.cpu.is_finished(self.program) {
+                writeln!(out, "the program has finished")?;
+                break;
+            }
+            if self.run_until(out, |cpu| !by_instruction || cpu.current.is_none())? {
+                break;
+            }
+        }
+        writeln!(out, "{}", status(self.program, &self.cpu))
+    }
+
+    /// Undo `n` cycles, or `n` instructions, from the snapshots.
+    fn rewind(&mut self, n: usize, by_instruction: bool, out: &mut impl Write) -> io::Result<()> {
+        for _ in 0..n {
+            let Some(cpu) = self.history.pop_back() else { break };
+            self.cpu = cpu;
+            // Keep going back to the start of the instruction, if we still have it.
+            while by_instruction && self.cpu.current.is_some() {
+                let Some(cpu) = self.history.pop_back() else { break };
+                self.cpu = cpu;
+            }
+        }
+        if self.history.is_empty() {
+            writeln!(out, "{}", if self.forgotten { "can't step back any further" } else { "at the start" })?;
+        }
+        writeln!(out, "{}", status(self.program, &self.cpu))
+    }
+
+    fn dispatch(&mut self, words: &[&str], out: &mut impl Write) -> Result<bool, CommandError> {
+        let count = || -> Result<usize, CommandError> {
+            words.get(1).map_or(Ok(1), |n| n.parse().map_err(|_| CommandError::Usage(format!("bad count '{}'", n))))
+        };
+        match words {
+            [] => { },
+            ["step" | "s", ..] => self.advance(count()?, true, out)?,
+            ["tick" | "t", ..] => self.advance(count()?, false, out)?,
+            ["continue" | "c"] => {
+                if self.cpu.is_finished(self.program) {
+                    writeln!(out, "the program has finished")?;
+                } else {
+                    self.run_until(out, |_| false)?;
+                }
+                writeln!(out, "{}", status(self.program, &self.cpu))?;
+            },
+            ["back", ..] => self.rewind(count()?, false, out)?,
+            ["rstep", ..] => self.rewind(count()?, true, out)?,
+            ["break", args @ ..] => {
+                let breakpoint = self.parse_breakpoint(args).map_err(CommandError::Usage)?;
+                self.breakpoints.push(breakpoint);
+                writeln!(out, "breakpoint {}: {}", self.breakpoints.len(), breakpoint)?;
+            },
+            ["breaks"] => {
+                for (idx, breakpoint) in self.breakpoints.iter().enumerate() {
+                    writeln!(out, "{}: {}", idx + 1, breakpoint)?;
+                }
+            },
+            ["delete", n] => match n.parse::<usize>() {
+                Ok(n) if (1..=self.breakpoints.len()).contains(&n) => { self.breakpoints.remove(n - 1); },
+                _ => return Err(CommandError::Usage(format!("no breakpoint '{}'", n)))
+            },
+            ["regs"] => {
+                let regs : Vec<String> = Register::ALL.iter().map(|&r| format!("{}={}", r, self.cpu.register(r))).collect();
+                writeln!(out, "{}", regs.join(" "))?;
+            },
+            ["print" | "p", register] => {
+                let register : Register = register.parse().map_err(|e| CommandError::Usage(format!("{}", e)))?;
+                writeln!(out, "{} = {}", register, self.cpu.register(register))?;
+            },
+            ["where"] => writeln!(out, "{}", status(self.program, &self.cpu))?,
+            ["screen"] => write!(out, "{}", crt::to_text(self.cpu.device.framebuffer()))?,
+            ["trace", "off"] => self.trace = None,
+            ["trace", path] => {
+                let file = File::create(path).map_err(|e| CommandError::Usage(format!("can't write '{}': {}", path, e)))?;
+                self.trace = Some(Box::new(BufWriter::new(file)));
+            },
+            ["help" | "h"] => writeln!(out, "{}", HELP)?,
+            ["quit" | "q"] => return Ok(false),
+            _ => return Err(CommandError::Usage(format!("unknown command '{}', try 'help'", words.join(" "))))
+        }
+        Ok(true)
+    }
+
+    /// Run one command, writing anything it has to say to `out`. Returns false on `quit`.
+    pub fn command(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let words : Vec<&str> = line.split_whitespace().collect();
+        match self.dispatch(&words, out) {
+            Ok(carry_on) => Ok(carry_on),
+            Err(CommandError::Usage(message)) => writeln!(out, "error: {}", message).map(|_| true),
+            Err(CommandError::Io(e)) => Err(e),
+        }
+    }
+
+    /// Read commands from `input` until it runs out or says `quit`, prompting if asked to.
+    pub fn repl(&mut self, input: impl BufRead, out: &mut impl Write, prompt: bool) -> io::Result<()> {
+        let mut lines = input.lines();
+        loop {
+            if prompt {
+                write!(out, "(elf) ")?;
+                out.flush()?;
+            }
+            let Some(line) = lines.next() else { break };
+            if !self.command(&line?, out)? {
+                break;
+            }
+        }
+        if let Some(trace) = self.trace.as_mut() {
+            trace.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// One line about where the CPU is: the next cycle, the instruction and registers.
+pub fn status(program: &Program, cpu: &Cpu) -> String {
+    let instruction = match cpu.current {
+        Some((instruction, done)) => format!("{} [{}/{}]", instruction, done, instruction.cycles()),
+        None if cpu.is_finished(program) => "(finished)".to_string(),
+        None => format!("{} [0/{}]", program[cpu.pc], program[cpu.pc].cycles()),
+    };
+    let regs : Vec<String> = Register::ALL.iter().map(|&r| format!("{}={}", r, cpu.register(r))).collect();
+    format!("cycle {} pc {} {} {}", cpu.cycle, cpu.pc, instruction, regs.join(" "))
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+    use crate::asm::assemble;
+
+    const PROGRAM: &str = "
+        noop
+        addx 3
+        set a, 2
+loop:   addx -5
+        add a, -1
+        jnz a, loop
+        halt";
+
+    fn session(script: &str) -> String {
+        let program = assemble(PROGRAM).unwrap();
+        let mut debugger = Debugger::new(&program);
+        let mut out = Vec::new();
+        debugger.repl(script.as_bytes(), &mut out, false).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_stepping() {
+        assert_eq!(session("where\ntick\nstep\ntick\nregs\nstep 2\nprint x"), "\
+cycle 1 pc 0 noop [0/1] x=1 a=0 b=0 c=0 d=0
+cycle 2 pc 1 addx 3 [0/2] x=1 a=0 b=0 c=0 d=0
+cycle 4 pc 2 set a, 2 [0/1] x=4 a=0 b=0 c=0 d=0
+cycle 5 pc 3 addx -5 [0/2] x=4 a=2 b=0 c=0 d=0
+x=4 a=2 b=0 c=0 d=0
+cycle 9 pc 5 jnz a, 3 [0/2] x=-1 a=1 b=0 c=0 d=0
+x = -1
+");
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let out = session("break pc loop\nbreak x < 0\nbreak cycle 3\nbreaks\ncontinue\ncontinue\ndelete 3\ncontinue\ncontinue\ncontinue");
+        assert_eq!(out, "\
+breakpoint 1: pc 3
+breakpoint 2: x < 0
+breakpoint 3: cycle 3
+1: pc 3
+2: x < 0
+3: cycle 3
+breakpoint 3 (cycle 3) hit
+cycle 3 pc 1 addx 3 [1/2] x=1 a=0 b=0 c=0 d=0
+breakpoint 1 (pc 3) hit
+cycle 5 pc 3 addx -5 [0/2] x=4 a=2 b=0 c=0 d=0
+breakpoint 2 (x < 0) hit
+cycle 7 pc 4 add a, -1 [0/2] x=-1 a=2 b=0 c=0 d=0
+breakpoint 1 (pc 3) hit
+cycle 11 pc 3 addx -5 [0/2] x=-1 a=1 b=0 c=0 d=0
+the program has finished
+cycle 18 pc 7 (finished) x=-6 a=0 b=0 c=0 d=0
+");
+    }
+
+    #[test]
+    fn test_reverse() {
+        let out = session("step 4\nback\nrstep\nrstep 10\nstep 100\nback 3");
+        assert_eq!(out, "\
+cycle 7 pc 4 add a, -1 [0/2] x=-1 a=2 b=0 c=0 d=0
+cycle 6 pc 3 addx -5 [1/2] x=4 a=2 b=0 c=0 d=0
+cycle 5 pc 3 addx -5 [0/2] x=4 a=2 b=0 c=0 d=0
+at the start
+cycle 1 pc 0 noop [0/1] x=1 a=0 b=0 c=0 d=0
+the program has finished
+cycle 18 pc 7 (finished) x=-6 a=0 b=0 c=0 d=0
+cycle 15 pc 5 jnz a, 3 [0/2] x=-6 a=0 b=0 c=0 d=0
+");
+    }
+
+    #[test]
+    fn test_overflow() {
+        let program = assemble("addx 2147483647\nnoop").unwrap();
+        let mut debugger = Debugger::new(&program);
+        let mut out = Vec::new();
+        debugger.repl("step\nstep".as_bytes(), &mut out, false).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+instruction 0 (addx 2147483647) overflowed working with 1 and 2147483647
+cycle 3 pc 0 (finished) x=1 a=0 b=0 c=0 d=0
+the program has finished
+cycle 3 pc 0 (finished) x=1 a=0 b=0 c=0 d=0
+");
+    }
+
+    #[test]
+    fn test_history_limit() {
+        let program = assemble(PROGRAM).unwrap();
+        let mut debugger = Debugger::new(&program);
+        debugger.history_limit = 2;
+        let mut out = Vec::new();
+        debugger.repl("step 3\nrstep\nrstep\nback".as_bytes(), &mut out, false).unwrap();
+        assert_eq!(debugger.history.len(), 0);
+        // Only the second half of the addx 3 is left to go back to.
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+cycle 5 pc 3 addx -5 [0/2] x=4 a=2 b=0 c=0 d=0
+cycle 4 pc 2 set a, 2 [0/1] x=4 a=0 b=0 c=0 d=0
+can't step back any further
+cycle 3 pc 1 addx 3 [1/2] x=1 a=0 b=0 c=0 d=0
+can't step back any further
+cycle 3 pc 1 addx 3 [1/2] x=1 a=0 b=0 c=0 d=0
+");
+    }
+
+    #[test]
+    fn test_trace_and_errors() {
+        let program = assemble(PROGRAM).unwrap();
+        let mut debugger = Debugger::new(&program);
+        let trace = tempfile::NamedTempFile::new().unwrap();
+        let script = format!("trace {}\nstep 2\ntrace off\nstep\nfrob\nbreak y > 1\nprint q\nstep x\nquit\nstep", trace.path().display());
+        let mut out = Vec::new();
+        debugger.repl(script.as_bytes(), &mut out, false).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+cycle 4 pc 2 set a, 2 [0/1] x=4 a=0 b=0 c=0 d=0
+cycle 5 pc 3 addx -5 [0/2] x=4 a=2 b=0 c=0 d=0
+error: unknown command 'frob', try 'help'
+error: no such register 'y'
+error: no such register 'q'
+error: bad count 'x'
+");
+        assert_eq!(std::fs::read_to_string(trace.path()).unwrap(), "\
+cycle 1 pc 0 noop [0/1] x=1 a=0 b=0 c=0 d=0
+cycle 2 pc 1 addx 3 [0/2] x=1 a=0 b=0 c=0 d=0
+cycle 3 pc 1 addx 3 [1/2] x=1 a=0 b=0 c=0 d=0
+");
+        assert_eq!(debugger.cpu.cycle, 5);
+    }
+}
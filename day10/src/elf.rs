@@ -1,49 +1,303 @@
+use std::fmt;
 use std::str::FromStr;
 use std::collections::{HashSet, HashMap};
-use std::num::ParseIntError;
-use std::io::{self, prelude::*};
-use colored::*;
 
-#[derive(Debug)]
+use crate::asm::{ErrorKind, Program};
+use crate::crt::{Crt, Device};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Register {
+    X,
+    A,
+    B,
+    C,
+    D
+}
+
+impl Register {
+    pub const ALL : [Register; 5] = [Register::X, Register::A, Register::B, Register::C, Register::D];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Register::X => "x",
+            Register::A => "a",
+            Register::B => "b",
+            Register::C => "c",
+            Register::D => "d",
+        }
+    }
+}
+
+impl FromStr for Register {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Register::ALL.into_iter()
+            .find(|register| register.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ErrorKind::BadRegister(s.to_string()))
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A register, or a number written into the instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Register(Register),
+    Value(i32)
+}
+
+impl FromStr for Operand {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<i32>() {
+            Ok(value) => Ok(Operand::Value(value)),
+            Err(_) if s.starts_with(|c: char| c == '-' || c.is_ascii_digit()) => Err(ErrorKind::BadValue(s.to_string())),
+            Err(_) => Ok(Operand::Register(s.parse()?))
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Register(register) => write!(f, "{}", register),
+            Operand::Value(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// When a jump is taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Always,
+    Zero(Register),
+    NotZero(Register),
+    Negative(Register),
+    Positive(Register)
+}
+
+impl Condition {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Condition::Always => "jmp",
+            Condition::Zero(_) => "jz",
+            Condition::NotZero(_) => "jnz",
+            Condition::Negative(_) => "jneg",
+            Condition::Positive(_) => "jpos",
+        }
+    }
+
+    pub fn register(&self) -> Option<Register> {
+        match *self {
+            Condition::Always => None,
+            Condition::Zero(r) | Condition::NotZero(r) | Condition::Negative(r) | Condition::Positive(r) => Some(r),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
     NoOp,
-    AddX(i32)
+    AddX(i32),
+    /// Copy the operand into the register.
+    Set(Register, Operand),
+    Add(Register, Operand),
+    Mul(Register, Operand),
+    /// Carry on from the instruction at `target` if the condition holds.
+    Jump { condition: Condition, target: usize },
+    /// Stop the CPU for good.
+    Halt
+}
+
+impl Instruction {
+    /// How many cycles the instruction takes. Its effect lands at the end of the last one.
+    pub fn cycles(&self) -> u32 {
+        match self {
+            Instruction::NoOp => 1,
+            Instruction::AddX(_) => 2,
+            Instruction::Set(..) => 1,
+            Instruction::Add(..) => 2,
+            Instruction::Mul(..) => 4,
+            Instruction::Jump { .. } => 2,
+            Instruction::Halt => 1,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::NoOp => "noop",
+            Instruction::AddX(_) => "addx",
+            Instruction::Set(..) => "set",
+            Instruction::Add(..) => "add",
+            Instruction::Mul(..) => "mul",
+            Instruction::Jump { condition, .. } => condition.mnemonic(),
+            Instruction::Halt => "halt",
+        }
+    }
+
+    /// Parse a mnemonic and its operands. Jump targets are looked up with `label`.
+    pub fn parse_with(mnemonic: &str, operands: &[&str], label: impl Fn(&str) -> Result<usize, ErrorKind>) -> Result<Self, ErrorKind> {
+        let expect = |count: usize| if operands.len() == count {
+            Ok(())
+        } else {
+            Err(ErrorKind::WrongOperandCount { mnemonic: mnemonic.to_string(), expected: count, found: operands.len() })
+        };
+        let value = |s: &str| s.parse::<i32>().map_err(|_| ErrorKind::BadValue(s.to_string()));
+        let jump = |condition: fn(Register) -> Condition| -> Result<Self, ErrorKind> {
+            expect(2)?;
+            Ok(Instruction::Jump { condition: condition(operands[0].parse()?), target: label(operands[1])? })
+        };
+
+        match mnemonic {
+            "noop" => { expect(0)?; Ok(Instruction::NoOp) },
+            "addx" => { expect(1)?; Ok(Instruction::AddX(value(operands[0])?)) },
+            "set" => { expect(2)?; Ok(Instruction::Set(operands[0].parse()?, operands[1].parse()?)) },
+            "add" => { expect(2)?; Ok(Instruction::Add(operands[0].parse()?, operands[1].parse()?)) },
+            "mul" => { expect(2)?; Ok(Instruction::Mul(operands[0].parse()?, operands[1].parse()?)) },
+            "jmp" => { expect(1)?; Ok(Instruction::Jump { condition: Condition::Always, target: label(operands[0])? }) },
+            "jz" => jump(Condition::Zero),
+            "jnz" => jump(Condition::NotZero),
+            "jneg" => jump(Condition::Negative),
+            "jpos" => jump(Condition::Positive),
+            "halt" => { expect(0)?; Ok(Instruction::Halt) },
+            _ => Err(ErrorKind::UnknownMnemonic(mnemonic.to_string()))
+        }
+    }
+
+    /// Write the instruction out, naming jump targets with `label`.
+    pub fn write_with(&self, f: &mut impl fmt::Write, label: impl Fn(usize) -> String) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        match self {
+            Instruction::NoOp | Instruction::Halt => Ok(()),
+            Instruction::AddX(value) => write!(f, " {}", value),
+            Instruction::Set(register, operand) | Instruction::Add(register, operand) | Instruction::Mul(register, operand) =>
+                write!(f, " {}, {}", register, operand),
+            Instruction::Jump { condition, target } => match condition.register() {
+                Some(register) => write!(f, " {}, {}", register, label(*target)),
+                None => write!(f, " {}", label(*target)),
+            }
+        }
+    }
 }
 
 impl FromStr for Instruction {
-    type Err = ParseIntError;
+    type Err = ErrorKind;
 
+    /// A single instruction, with jump targets given as instruction numbers.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<&str> = s.split(" ").collect();
-        match parts[0] {
-            "noop" => Ok(Self::NoOp),
-            "addx" => {
-                let x : i32 = parts[1].parse()?;
-                Ok(Self::AddX(x))
-            },
-            _ => unreachable!()
+        let (mnemonic, operands) = crate::asm::split_instruction(s);
+        Instruction::parse_with(mnemonic, &operands, |target| {
+            target.parse().map_err(|_| ErrorKind::UndefinedLabel(target.to_string()))
+        })
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_with(f, |target| target.to_string())
+    }
+}
+
+/// An instruction whose result didn't fit in its register. The register is left as it was and
+/// the CPU halts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overflow {
+    pub pc: usize,
+    pub instruction: Instruction,
+    pub lhs: i32,
+    pub rhs: i32,
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "instruction {} ({}) overflowed working with {} and {}", self.pc, self.instruction, self.lhs, self.rhs)
+    }
+}
+
+impl std::error::Error for Overflow {}
+
+/// Why the CPU stopped before the program finished. It halts in every case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    Overflow(Overflow),
+    /// `run` was still going after this many cycles, so the program probably loops forever.
+    CycleLimit(u32),
+    /// The cycle counter itself ran out of room.
+    ClockOverflow,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::Overflow(overflow) => overflow.fmt(f),
+            Fault::CycleLimit(limit) => write!(f, "still running after {} cycles", limit),
+            Fault::ClockOverflow => write!(f, "ran out of cycle numbers"),
         }
     }
 }
 
-#[derive(Default, Debug)]
-pub struct CPU {
+impl std::error::Error for Fault {}
+
+impl From<Overflow> for Fault {
+    fn from(overflow: Overflow) -> Self {
+        Fault::Overflow(overflow)
+    }
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct Cpu<D: Device = Crt> {
     pub cycle: u32,
-    pub register_x: i32,
+    pub registers: [i32; Register::ALL.len()],
+    /// Index of the next instruction to start.
+    pub pc: usize,
+    pub halted: bool,
+    /// The instruction that's part way through, and how many of its cycles are done.
+    pub current: Option<(Instruction, u32)>,
     pub watchpoints: HashSet<u32>,
-    pub watchvalues: HashMap<u32, i32>
+    pub watchvalues: HashMap<u32, i32>,
+    pub device: D
 }
 
-impl CPU {
-    const SCREEN_WIDTH : u32 = 40;
-    const CHAR_WIDTH : u32 = 5;
-
+impl Cpu {
     pub fn new() -> Self {
+        Self::with_device(Crt::default())
+    }
+}
+
+impl<D: Device> Cpu<D> {
+    pub fn with_device(device: D) -> Self {
+        let mut registers = [0; Register::ALL.len()];
+        registers[Register::X as usize] = 1;
         Self {
             cycle: 1,
-            register_x: 1,
+            registers,
+            pc: 0,
+            halted: false,
+            current: None,
             watchpoints: HashSet::new(),
             watchvalues: HashMap::new(),
+            device
+        }
+    }
+
+    pub fn register(&self, register: Register) -> i32 {
+        self.registers[register as usize]
+    }
+
+    pub fn register_x(&self) -> i32 {
+        self.register(Register::X)
+    }
+
+    fn value(&self, operand: Operand) -> i32 {
+        match operand {
+            Operand::Register(register) => self.register(register),
+            Operand::Value(value) => value,
         }
     }
 
@@ -51,51 +305,95 @@ impl CPU {
         self.watchpoints.insert(watch);
     }
 
-    fn do_cycle(&mut self) {
+    fn do_cycle(&mut self) -> Result<(), Fault> {
+        let Some(next) = self.cycle.checked_add(1) else {
+            self.halted = true;
+            return Err(Fault::ClockOverflow);
+        };
+
         // Check for any watchpoints
         if self.watchpoints.contains(&self.cycle) {
-            self.watchvalues.insert(self.cycle, self.register_x);
-        }
-
-        // Use one color per character because we cool that way.
-        let scan_pos = ((self.cycle-1) % CPU::SCREEN_WIDTH) as i32;
-        const RAINBOW_PLUS_WHITE : [colored::Color; 8] = [
-            Color::TrueColor {r: 255, g: 0, b: 0},
-            Color::TrueColor {r: 255, g: 127, b: 0},
-            Color::TrueColor {r: 255, g: 255, b: 0},
-            Color::TrueColor {r: 0, g: 255, b: 0},
-            Color::TrueColor {r: 0, g: 0, b: 255},
-            Color::TrueColor {r: 75, g: 0, b: 130},
-            Color::TrueColor {r: 148, g: 0, b: 211},
-            Color::TrueColor {r: 255, g: 255, b: 255},
-        ];
-        let color = RAINBOW_PLUS_WHITE[(scan_pos / CPU::CHAR_WIDTH as i32) as usize];
-
-        // Print values as appropriate
-        if scan_pos == 0 { println!(); }
-        if (self.register_x - scan_pos).abs() <= 1 {
-            print!("{}", "█".color(color));
-            io::stdout().flush().unwrap();
-        } else {
-            print!(" ");
-            io::stdout().flush().unwrap();
+            self.watchvalues.insert(self.cycle, self.register_x());
         }
 
+        self.device.tick(self.cycle, self.register_x());
+
         // Increment program counter
-        self.cycle += 1;
+        self.cycle = next;
+        Ok(())
     }
 
-    pub fn execute(&mut self, instruction: &Instruction) {
-        match instruction {
-            Instruction::NoOp => {
-                self.do_cycle();
+    /// Set `register` to `op` of its value and `rhs`, or halt if the result doesn't fit.
+    fn arithmetic(&mut self, instruction: &Instruction, register: Register, rhs: i32, op: fn(i32, i32) -> Option<i32>) -> Result<(), Overflow> {
+        let lhs = self.register(register);
+        match op(lhs, rhs) {
+            Some(value) => {
+                self.registers[register as usize] = value;
+                Ok(())
             },
+            None => {
+                self.halted = true;
+                Err(Overflow { pc: self.pc, instruction: *instruction, lhs, rhs })
+            }
+        }
+    }
+
+    /// Apply an instruction once all its cycles have gone by, and move on to the next one.
+    fn complete(&mut self, instruction: &Instruction) -> Result<(), Overflow> {
+        let mut next = self.pc + 1;
+        match *instruction {
+            Instruction::NoOp => { },
+            Instruction::AddX(arg) => self.arithmetic(instruction, Register::X, arg, i32::checked_add)?,
+            Instruction::Set(register, operand) => self.registers[register as usize] = self.value(operand),
+            Instruction::Add(register, operand) => self.arithmetic(instruction, register, self.value(operand), i32::checked_add)?,
+            Instruction::Mul(register, operand) => self.arithmetic(instruction, register, self.value(operand), i32::checked_mul)?,
+            Instruction::Jump { condition, target } => {
+                let taken = match condition {
+                    Condition::Always => true,
+                    Condition::Zero(r) => self.register(r) == 0,
+                    Condition::NotZero(r) => self.register(r) != 0,
+                    Condition::Negative(r) => self.register(r) < 0,
+                    Condition::Positive(r) => self.register(r) > 0,
+                };
+                if taken { next = target; }
+            },
+            Instruction::Halt => self.halted = true,
+        }
+        self.pc = next;
+        Ok(())
+    }
+
+    /// Whether there's nothing more to run: the program halted or ran off the end.
+    pub fn is_finished(&self, program: &Program) -> bool {
+        self.halted || (self.current.is_none() && self.pc >= program.len())
+    }
+
+    /// Run one clock cycle of `program`, starting the next instruction if need be. Returns
+    /// false if the program had already finished.
+    pub fn tick(&mut self, program: &Program) -> Result<bool, Fault> {
+        if self.is_finished(program) {
+            return Ok(false);
+        }
+        let (instruction, done) = self.current.take().unwrap_or((program[self.pc], 0));
+        self.do_cycle()?;
+        if done + 1 < instruction.cycles() {
+            self.current = Some((instruction, done + 1));
+        } else {
+            self.complete(&instruction)?;
+        }
+        Ok(true)
+    }
 
-            Instruction::AddX(arg) => {
-                self.do_cycle();
-                self.do_cycle();
-                self.register_x += arg;
+    /// Run to the end of the program, giving up once the cycle count passes `max_cycles` in
+    /// case it never gets there.
+    pub fn run(&mut self, program: &Program, max_cycles: u32) -> Result<(), Fault> {
+        while !self.is_finished(program) {
+            if self.cycle > max_cycles {
+                self.halted = true;
+                return Err(Fault::CycleLimit(max_cycles));
             }
+            self.tick(program)?;
         }
+        Ok(())
     }
 }
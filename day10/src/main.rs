@@ -1,37 +1,155 @@
-use std::fs::File;
-use std::io::{self, prelude::*, BufReader};
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
 
+mod asm;
+mod crt;
+mod debugger;
 mod elf;
 
-pub fn day10() {
-    let file = File::open("input.txt").expect("File 'input.txt' not readable.");
+use crt::{Framebuffer, Screen};
 
-    let mut cpu = elf::CPU::new();
+/// Far more than a frame needs, but few enough that a program stuck in a loop gives up quickly.
+const MAX_CYCLES : u32 = 10_000_000;
+
+/// Run a program from the start, giving the part 1 signal strength total and the screen.
+pub fn run(program: &asm::Program) -> Result<(i32, Framebuffer), elf::Fault> {
+    let mut cpu = elf::Cpu::new();
     // Add Watchpoints for Part 1
     [20, 60, 100, 140, 180, 220].iter().for_each(|w| cpu.add_watchpoint(*w));
 
-    let reader = BufReader::new(file)
-        .lines() // Get a line iterator
-        .filter_map(|line| line.ok()); // Get Strings instead of Result
-
-    // Print top of screen
-    print!("\n{}", "-".repeat(40));
-    // Execute Program
-    for line in reader {
-        cpu.execute(&line.parse().expect("Failed to parse input line."));
-    }
-    // Print bottom of screen
-    println!("\n{}\n", "-".repeat(40));
+    cpu.run(program, MAX_CYCLES)?;
 
-    // Compute and print total for part 1
     let part1_total : i32 = cpu.watchvalues.iter().map(|(cyc, x)| *cyc as i32 * x).sum();
+    Ok((part1_total, *cpu.device.framebuffer()))
+}
+
+/// Assemble a program, or give up with every error in it.
+fn load(path: &str) -> asm::Program {
+    let source = fs::read_to_string(path).unwrap_or_else(|_| panic!("File '{}' not readable.", path));
+    asm::assemble(&source).unwrap_or_else(|errors| {
+        errors.iter().for_each(|e| eprintln!("{}: {}", path, e));
+        std::process::exit(2);
+    })
+}
+
+pub fn day10(screen: &mut dyn Screen, path: &str) -> io::Result<()> {
+    let program = load(path);
+    let (part1_total, framebuffer) = run(&program).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        std::process::exit(1);
+    });
+
+    screen.show(&framebuffer)?;
     println!("Part 1 Total: {}", part1_total);
+    match ocr::read(&framebuffer) {
+        Ok(text) => println!("Part 2: {}", text),
+        Err(e) => eprintln!("Part 2: {}", e),
+    }
+    Ok(())
+}
+
+const USAGE: &str = "usage: day10 [--headless | --text | --png FILE] [--program FILE]
+       day10 --disasm [FILE]
+       day10 --debug [--trace FILE] [--program FILE]   (commands on stdin, try 'help')";
+
+/// Debug a program with commands from stdin, tracing every cycle to `trace` if given.
+fn debug(path: &str, trace: Option<&str>) -> io::Result<()> {
+    let program = load(path);
+    let mut debugger = debugger::Debugger::new(&program);
+    if let Some(trace) = trace {
+        debugger.set_trace(Some(Box::new(io::BufWriter::new(fs::File::create(trace)?))));
+    }
+    let stdin = io::stdin();
+    let prompt = stdin.is_terminal();
+    debugger.repl(stdin.lock(), &mut io::stdout(), prompt)
 }
 
 pub fn main() -> io::Result<()> {
-    day10();
-    Ok(())
+    let args : Vec<String> = env::args().skip(1).collect();
+    let mut args : Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+    let mut path = "input.txt";
+    if let Some(idx) = args.iter().position(|&arg| arg == "--program") {
+        if idx + 1 < args.len() {
+            path = args.remove(idx + 1);
+            args.remove(idx);
+        }
+    }
+
+    let mut screen : Box<dyn Screen> = match args[..] {
+        [] => Box::new(crt::TerminalScreen::new(io::stdout())),
+        ["--headless"] => Box::new(crt::NullScreen),
+        ["--text"] => Box::new(crt::TextScreen::new(io::stdout())),
+        ["--png", path] => Box::new(crt::PngScreen { path: PathBuf::from(path), scale: 10 }),
+        ["--debug"] => return debug(path, None),
+        ["--debug", "--trace", trace] => return debug(path, Some(trace)),
+        ["--disasm"] | ["--disasm", _] => {
+            print!("{}", asm::disassemble(&load(args.get(1).copied().unwrap_or(path))));
+            return Ok(());
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    day10(screen.as_mut(), path)
 }
 
 // 14320
-// PCPBKAPJ
\ No newline at end of file
+// PCPBKAPJ
+#[cfg(test)]
+ mod test {
+    use super::*;
+    use crt::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    #[test]
+    fn test_sprite_follows_x() {
+        // X sits at 1 for the first row and the start of the second, then jumps out of the
+        // way just as the program ends, leaving the rest of the screen dark.
+        let mut program = "noop\n".repeat(40);
+        program += "addx 39\n";
+        let (_, framebuffer) = run(&asm::assemble(&program).unwrap()).unwrap();
+        assert!(framebuffer[0][2] && !framebuffer[0][3]);
+        let text = crt::to_text(&framebuffer);
+        let rows : Vec<&str> = text.lines().collect();
+        assert_eq!(rows[0], format!("###{}", ".".repeat(37)));
+        assert_eq!(rows[1], format!("##{}", ".".repeat(38)));
+        assert_eq!(rows[2], ".".repeat(40));
+    }
+
+    #[test]
+    fn test_letters() {
+        // Draw an "L" on the second pass of the beam, so the first cycles (where X is always 1)
+        // get drawn over: park the sprite just off the left edge for five rows, then drag it
+        // along the bottom row with the beam for four pixels and throw it out of the way.
+        let mut program = "addx -2\n".to_string();
+        program += &"noop\n".repeat(437);
+        program += "addx 2\naddx 1\naddx 50\n";
+        let (_, framebuffer) = run(&asm::assemble(&program).unwrap()).unwrap();
+        assert_eq!(crt::to_text(&framebuffer).lines().last().unwrap(), format!("####{}", ".".repeat(36)));
+        assert_eq!(ocr::read(&framebuffer), Ok("L".to_string()));
+    }
+
+    #[test]
+    fn test_headless() {
+        let mut cpu = elf::Cpu::with_device(());
+        cpu.run(&asm::Program::new(vec!["addx 5".parse().unwrap()]), 10).unwrap();
+        assert_eq!((cpu.cycle, cpu.register_x()), (3, 6));
+        assert!(crt::NullScreen.show(&[[true; SCREEN_WIDTH]; SCREEN_HEIGHT]).is_ok());
+    }
+
+    #[test]
+    fn test_png() {
+        let mut framebuffer = [[false; SCREEN_WIDTH]; SCREEN_HEIGHT];
+        framebuffer[1][2] = true;
+        let mut bytes = Vec::new();
+        crt::PngScreen::encode(&framebuffer, &mut bytes, 2).unwrap();
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (80, 12));
+        assert_eq!(pixels[2 * 80 + 4], 0xe0);
+        assert_eq!(pixels[2 * 80 + 3], 0x10);
+    }
+}
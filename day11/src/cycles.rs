@@ -0,0 +1,194 @@
+use std::collections::hash_map::{Entry, HashMap};
+use std::fmt;
+
+use crate::{Monkey, RoundError};
+use crate::expr::EvalError;
+use crate::worry::Normalize;
+
+/// Why the inspections over a long game couldn't be counted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CycleError {
+    /// The arithmetic went wrong following an item round its loop.
+    Round(RoundError),
+    /// The monkey at this position makes more inspections than fit in a `u64`.
+    Overflow { monkey: usize },
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CycleError::Round(e) => e.fmt(f),
+            CycleError::Overflow { monkey } => write!(f, "counting monkey {}'s inspections, which don't fit in 64 bits", monkey),
+        }
+    }
+}
+
+impl std::error::Error for CycleError {}
+
+impl From<RoundError> for CycleError {
+    fn from(e: RoundError) -> Self {
+        CycleError::Round(e)
+    }
+}
+
+/// One item's journey, followed on its own until it comes back to a state it's been in before.
+///
+/// Items never affect each other: where an item goes and what happens to its worry level only
+/// depend on which monkey holds it and its worry level at the start of the round. Keeping worry
+/// levels modulo the LCM of the tests leaves finitely many of those states, so every item ends
+/// up going round a loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemCycle {
+    /// The first round of the loop, counting the start of the game as 0.
+    pub start: usize,
+    /// How many rounds it takes to get back round.
+    pub length: usize,
+    num_monkeys: usize,
+    /// How many times each monkey had inspected the item after each round up to
+    /// `start + length`, one row of `num_monkeys` counts after another.
+    history: Vec<u64>,
+}
+
+impl ItemCycle {
+    /// Follow an item that starts the game with monkey `holder`, keeping worry levels modulo
+    /// `modulus` as [`reduction_modulus`](crate::reduction_modulus) would.
+    pub fn find(monkeys: &[Monkey], holder: usize, worry: u64, modulus: u64) -> Result<Self, RoundError> {
+        let normalize = Normalize::Reduce(modulus);
+        let num_monkeys = monkeys.len();
+        let mut state = (holder, normalize.apply(worry));
+        let mut seen = HashMap::new();
+        let mut history = vec![0; num_monkeys];
+
+        for round in 0.. {
+            if let Some(start) = seen.insert(state, round) {
+                return Ok(Self { start, length: round - start, num_monkeys, history });
+            }
+            history.extend_from_within(round * num_monkeys..);
+            let counts = &mut history[(round + 1) * num_monkeys..];
+            state = play_round(monkeys, state, &normalize, counts)
+                .map_err(|(monkey, worry, error)| RoundError { round: round as u32 + 1, monkey, worry: worry.to_string(), error: error.into() })?;
+        }
+        unreachable!()
+    }
+
+    fn counts_after(&self, round: usize) -> &[u64] {
+        &self.history[round * self.num_monkeys..(round + 1) * self.num_monkeys]
+    }
+
+    /// How many times each monkey inspects the item over the first `rounds` rounds.
+    pub fn inspections_after(&self, rounds: u64) -> Result<Vec<u64>, CycleError> {
+        let (start, length) = (self.start as u64, self.length as u64);
+        if rounds <= start + length {
+            return Ok(self.counts_after(rounds as usize).to_vec());
+        }
+        let (loops, rest) = ((rounds - start) / length, (rounds - start) % length);
+        let before = self.counts_after(self.start);
+        let after = self.counts_after(self.start + self.length);
+        self.counts_after(self.start + rest as usize).iter().zip(before.iter().zip(after)).enumerate()
+            .map(|(monkey, (count, (before, after)))| loops.checked_mul(after - before)
+                .and_then(|more| more.checked_add(*count))
+                .ok_or(CycleError::Overflow { monkey }))
+            .collect()
+    }
+}
+
+/// Take an item through one round, adding its inspections to `counts`. An item thrown to a monkey
+/// further down the line gets inspected again in the same round; otherwise it waits for the next.
+/// If the arithmetic goes wrong, gives the monkey and worry level it went wrong on.
+fn play_round(monkeys: &[Monkey], (mut holder, mut worry): (usize, u64), normalize: &Normalize, counts: &mut [u64])
+    -> Result<(usize, u64), (u32, u64, EvalError)> {
+    loop {
+        let monkey = &monkeys[holder];
+        let inspect = |worry: &u64| -> Result<(u64, bool), EvalError> {
+            let new = normalize.apply(monkey.operation.evaluate(worry)?);
+            let passed = monkey.test.evaluate(&new)?;
+            Ok((new, passed))
+        };
+        let (new, passed) = inspect(&worry).map_err(|error| (monkey.id, worry, error))?;
+        let dest = if passed { monkey.test_true_dest } else { monkey.test_false_dest };
+        counts[holder] += 1;
+        worry = new;
+        let again = dest > holder;
+        holder = dest;
+        if !again {
+            return Ok((holder, worry));
+        }
+    }
+}
+
+/// How many times each monkey inspects an item over `rounds` rounds, which can be far more
+/// than could be played one at a time.
+pub fn inspections_after(monkeys: &[Monkey], modulus: u64, rounds: u64) -> Result<Vec<u64>, CycleError> {
+    let mut totals = vec![0u64; monkeys.len()];
+    // Items that start out in the same state go the same way, so only follow each state once.
+    let mut cycles: HashMap<(usize, u64), ItemCycle> = HashMap::new();
+    for (holder, monkey) in monkeys.iter().enumerate() {
+        for &worry in &monkey.items {
+            let key = (holder, worry % modulus);
+            let cycle = match cycles.entry(key) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(ItemCycle::find(monkeys, holder, worry, modulus)?),
+            };
+            for (monkey, (total, count)) in totals.iter_mut().zip(cycle.inspections_after(rounds)?).enumerate() {
+                *total = total.checked_add(count).ok_or(CycleError::Overflow { monkey })?;
+            }
+        }
+    }
+    Ok(totals)
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+    use crate::{play, reduction_modulus};
+
+    fn sample() -> Vec<Monkey> {
+        crate::test::SAMPLE.split("\n\n").map(|def| def.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_against_brute_force() {
+        let monkeys = sample();
+        let modulus = reduction_modulus(&monkeys).unwrap();
+        let mut played = monkeys.clone();
+        for rounds in 0..300 {
+            let counts: Vec<u64> = played.iter().map(|m| m.num_inspections).collect();
+            assert_eq!(inspections_after(&monkeys, modulus, rounds).unwrap(), counts, "after {} rounds", rounds);
+            play(&mut played, 1, &Normalize::Reduce(modulus)).unwrap();
+        }
+
+        let counts = inspections_after(&monkeys, modulus, 10000).unwrap();
+        assert_eq!(counts, vec![52166, 47830, 1938, 52013]);
+        assert_eq!(crate::monkey_business(counts), 2713310158);
+    }
+
+    #[test]
+    fn test_item_cycle() {
+        let monkeys = sample();
+        let modulus = reduction_modulus(&monkeys).unwrap();
+        let cycle = ItemCycle::find(&monkeys, 0, 79, modulus).unwrap();
+        assert!(cycle.length > 0);
+
+        // Going round the loop once more adds the same inspections as the first time round.
+        let start = cycle.start as u64;
+        let length = cycle.length as u64;
+        let at_start = cycle.inspections_after(start).unwrap();
+        let once: Vec<u64> = cycle.inspections_after(start + length).unwrap().iter()
+            .zip(&at_start).map(|(a, b)| a - b).collect();
+        let twice: Vec<u64> = cycle.inspections_after(start + 2 * length).unwrap().iter()
+            .zip(&at_start).map(|(a, b)| a - b).collect();
+        assert_eq!(twice, once.iter().map(|n| 2 * n).collect::<Vec<u64>>());
+
+        // A trillion rounds only needs the one loop.
+        let counts = inspections_after(&monkeys, modulus, 1_000_000_000_000).unwrap();
+        assert!(counts.iter().sum::<u64>() > 1_000_000_000_000);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let monkeys = sample();
+        let modulus = reduction_modulus(&monkeys).unwrap();
+        // Every item gets inspected at least once a round, so ten of them can't fit.
+        assert!(matches!(inspections_after(&monkeys, modulus, u64::MAX), Err(CycleError::Overflow { .. })));
+    }
+}
@@ -0,0 +1,380 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::worry::Worry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinOp {
+    pub fn symbol(&self) -> char {
+        match self {
+            BinOp::Add => '+',
+            BinOp::Sub => '-',
+            BinOp::Mul => '*',
+            BinOp::Div => '/',
+            BinOp::Rem => '%',
+        }
+    }
+}
+
+/// Arithmetic on a worry level, like `old * (old + 3) % 7`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// The worry level the expression is about.
+    Var,
+    Literal(u64),
+    Binary(Box<Expr>, BinOp, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    /// The result of `lhs op rhs` doesn't fit in a `u64`, either way.
+    Overflow { op: BinOp, lhs: u64, rhs: u64 },
+    /// A subtraction that would go below zero, when there's no upper limit to overflow.
+    Negative,
+    DivisionByZero,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::Overflow { op, lhs, rhs } => write!(f, "{} {} {} is out of range", lhs, op.symbol(), rhs),
+            EvalError::Negative => write!(f, "worry levels can't go below zero"),
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset into the text where things went wrong.
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Expr {
+    /// Parse `text`, where the worry level is called `var`. The usual precedence applies:
+    /// `*`, `/` and `%` bind tighter than `+` and `-`, and everything is left associative.
+    pub fn parse(text: &str, var: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser { text, chars: text.char_indices().peekable(), var };
+        let expr = parser.sum()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some((position, c)) => Err(ParseError { position, message: format!("unexpected '{}'", c) }),
+        }
+    }
+
+    pub fn evaluate<W: Worry>(&self, var: &W) -> Result<W, EvalError> {
+        match self {
+            Expr::Var => Ok(var.clone()),
+            Expr::Literal(value) => Ok(W::from_u64(*value)),
+            Expr::Binary(lhs, op, rhs) => W::apply(*op, &lhs.evaluate(var)?, &rhs.evaluate(var)?),
+        }
+    }
+
+    /// Whether working modulo some number before evaluating gives the same answer modulo that
+    /// number as working it out in full, which is true of anything built from `+` and `*`.
+    pub fn is_modular(&self) -> bool {
+        match self {
+            Expr::Var | Expr::Literal(_) => true,
+            Expr::Binary(lhs, BinOp::Add | BinOp::Mul, rhs) => lhs.is_modular() && rhs.is_modular(),
+            Expr::Binary(..) => false,
+        }
+    }
+
+    /// Write the expression out with the worry level called `var`, bracketing only where needed.
+    pub fn write_with(&self, f: &mut impl fmt::Write, var: &str) -> fmt::Result {
+        match self {
+            Expr::Var => write!(f, "{}", var),
+            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Binary(lhs, op, rhs) => {
+                let tight = |op: &BinOp| matches!(op, BinOp::Mul | BinOp::Div | BinOp::Rem);
+                // A sum inside a product needs brackets, and so does anything on the right of
+                // an operator at the same level, since the grouping is to the left.
+                let bracket_left = matches!(**lhs, Expr::Binary(_, ref inner, _) if tight(op) && !tight(inner));
+                let bracket_right = matches!(**rhs, Expr::Binary(_, ref inner, _) if tight(op) || !tight(inner));
+                write_side(f, lhs, var, bracket_left)?;
+                write!(f, " {} ", op.symbol())?;
+                write_side(f, rhs, var, bracket_right)
+            }
+        }
+    }
+}
+
+fn write_side(f: &mut impl fmt::Write, expr: &Expr, var: &str, bracket: bool) -> fmt::Result {
+    if bracket { write!(f, "(")?; }
+    expr.write_with(f, var)?;
+    if bracket { write!(f, ")")?; }
+    Ok(())
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_with(f, "old")
+    }
+}
+
+/// Recursive descent over the characters, one function per precedence level.
+struct Parser<'a> {
+    text: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    var: &'a str,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<(usize, char)> {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn end(&self) -> usize {
+        self.text.len()
+    }
+
+    fn binary(&mut self, ops: &[BinOp], operand: fn(&mut Self) -> Result<Expr, ParseError>) -> Result<Expr, ParseError> {
+        let mut lhs = operand(self)?;
+        while let Some(op) = self.peek().and_then(|(_, c)| ops.iter().find(|op| op.symbol() == c)) {
+            self.chars.next();
+            lhs = Expr::Binary(Box::new(lhs), *op, Box::new(operand(self)?));
+        }
+        Ok(lhs)
+    }
+
+    fn sum(&mut self) -> Result<Expr, ParseError> {
+        self.binary(&[BinOp::Add, BinOp::Sub], Self::product)
+    }
+
+    fn product(&mut self) -> Result<Expr, ParseError> {
+        self.binary(&[BinOp::Mul, BinOp::Div, BinOp::Rem], Self::atom)
+    }
+
+    fn atom(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Some((_, '(')) => {
+                self.chars.next();
+                let expr = self.sum()?;
+                match self.peek() {
+                    Some((_, ')')) => { self.chars.next(); Ok(expr) },
+                    Some((position, c)) => Err(ParseError { position, message: format!("expected ')', found '{}'", c) }),
+                    None => Err(ParseError { position: self.end(), message: "missing ')'".to_string() }),
+                }
+            },
+            Some((start, c)) if c.is_ascii_alphanumeric() => {
+                let mut end = start;
+                while let Some((idx, c)) = self.chars.next_if(|(_, c)| c.is_ascii_alphanumeric()) {
+                    end = idx + c.len_utf8();
+                }
+                let word = &self.text[start..end];
+                if word == self.var {
+                    Ok(Expr::Var)
+                } else if word.bytes().all(|b| b.is_ascii_digit()) {
+                    word.parse().map(Expr::Literal)
+                        .map_err(|_| ParseError { position: start, message: format!("{} is too big", word) })
+                } else {
+                    Err(ParseError { position: start, message: format!("unknown name '{}', expected '{}' or a number", word, self.var) })
+                }
+            },
+            Some((position, c)) => Err(ParseError { position, message: format!("unexpected '{}'", c) }),
+            None => Err(ParseError { position: self.end(), message: "expression ends too soon".to_string() }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    /// Longest first, so `<=` isn't read as `<`.
+    const SYMBOLS: [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Eq), ("!=", Comparison::Ne), ("<=", Comparison::Le),
+        (">=", Comparison::Ge), ("<", Comparison::Lt), (">", Comparison::Gt),
+    ];
+
+    fn holds<W: Ord>(&self, lhs: W, rhs: W) -> bool {
+        match self {
+            Comparison::Eq => lhs == rhs,
+            Comparison::Ne => lhs != rhs,
+            Comparison::Lt => lhs < rhs,
+            Comparison::Le => lhs <= rhs,
+            Comparison::Gt => lhs > rhs,
+            Comparison::Ge => lhs >= rhs,
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        Comparison::SYMBOLS.iter().find(|(_, c)| c == self).unwrap().0
+    }
+}
+
+/// How a monkey decides where to throw an item, from the item's new worry level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Predicate {
+    /// The puzzle's `divisible by N`.
+    DivisibleBy(u64),
+    /// Anything else, like `new % 7 == 3` or `new > 1000`.
+    Compare(Expr, Comparison, Expr),
+}
+
+impl Predicate {
+    /// Parse what follows `Test: `, where the new worry level is called `new`.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let text = text.trim();
+        if let Some(divisor) = text.strip_prefix("divisible by ") {
+            return match divisor.trim().parse() {
+                Ok(0) | Err(_) => Err(ParseError { position: 13, message: format!("bad divisor '{}'", divisor) }),
+                Ok(divisor) => Ok(Predicate::DivisibleBy(divisor)),
+            };
+        }
+        let (position, symbol, comparison) = Comparison::SYMBOLS.iter()
+            .filter_map(|&(symbol, comparison)| text.find(symbol).map(|position| (position, symbol, comparison)))
+            .min_by_key(|&(position, symbol, _)| (position, std::cmp::Reverse(symbol.len())))
+            .ok_or(ParseError { position: 0, message: "expected 'divisible by N' or a comparison".to_string() })?;
+        let shifted = |e: ParseError, offset: usize| ParseError { position: e.position + offset, ..e };
+        let lhs = Expr::parse(&text[..position], "new")?;
+        let rhs = Expr::parse(&text[position + symbol.len()..], "new").map_err(|e| shifted(e, position + symbol.len()))?;
+        Ok(Predicate::Compare(lhs, comparison, rhs))
+    }
+
+    pub fn evaluate<W: Worry>(&self, new: &W) -> Result<bool, EvalError> {
+        match self {
+            Predicate::DivisibleBy(divisor) => Ok(new.rem_u64(*divisor) == 0),
+            Predicate::Compare(lhs, comparison, rhs) => Ok(comparison.holds(lhs.evaluate(new)?, rhs.evaluate(new)?)),
+        }
+    }
+
+    /// A number the worry level can be reduced modulo without changing the outcome, if there is
+    /// one: the divisor, or `m` for a test like `new % m == k`.
+    pub fn modulus(&self) -> Option<u64> {
+        match self {
+            Predicate::DivisibleBy(divisor) => Some(*divisor),
+            Predicate::Compare(lhs, Comparison::Eq | Comparison::Ne, rhs) => match (lhs, rhs) {
+                (Expr::Binary(inner, BinOp::Rem, modulus), Expr::Literal(_))
+                | (Expr::Literal(_), Expr::Binary(inner, BinOp::Rem, modulus)) => match **modulus {
+                    Expr::Literal(modulus) if modulus > 0 && inner.is_modular() => Some(modulus),
+                    _ => None,
+                },
+                _ => None,
+            },
+            Predicate::Compare(..) => None,
+        }
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Predicate::DivisibleBy(divisor) => write!(f, "divisible by {}", divisor),
+            Predicate::Compare(lhs, comparison, rhs) => {
+                lhs.write_with(f, "new")?;
+                write!(f, " {} ", comparison.symbol())?;
+                rhs.write_with(f, "new")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use num_bigint::BigUint;
+
+    fn eval(text: &str, old: u64) -> Result<u64, EvalError> {
+        Expr::parse(text, "old").unwrap().evaluate(&old)
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(eval("old * 19", 79), Ok(1501));
+        assert_eq!(eval("old + old", 4), Ok(8));
+        assert_eq!(eval("old * old", 9), Ok(81));
+        assert_eq!(eval("2 + 3 * 4", 0), Ok(14));
+        assert_eq!(eval("(2 + 3) * 4", 0), Ok(20));
+        assert_eq!(eval("100 - 10 - 5", 0), Ok(85));
+        assert_eq!(eval("old / 2 % 5", 17), Ok(3));
+        assert_eq!(eval("((old))", 7), Ok(7));
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        assert_eq!(eval("old * old", 1 << 32), Err(EvalError::Overflow { op: BinOp::Mul, lhs: 1 << 32, rhs: 1 << 32 }));
+        assert_eq!(eval("3 - old", 4), Err(EvalError::Overflow { op: BinOp::Sub, lhs: 3, rhs: 4 }));
+        assert_eq!(eval("old % (old - 5)", 5), Err(EvalError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_big_evaluate() {
+        let expr = Expr::parse("old * old * old + 1", "old").unwrap();
+        let old = BigUint::from(1u64 << 40);
+        assert_eq!(expr.evaluate(&old), Ok(BigUint::from(1u8) + (BigUint::from(1u8) << 120)));
+        assert_eq!(Expr::parse("1 - old", "old").unwrap().evaluate(&old), Err(EvalError::Negative));
+        assert!(!Expr::parse("old * old - old", "old").unwrap().is_modular());
+        assert!(Expr::parse("(old + 3) * old", "old").unwrap().is_modular());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |text: &str| Expr::parse(text, "old").unwrap_err();
+        assert_eq!(error("old *").position, 5);
+        assert_eq!(error("(old + 1"), ParseError { position: 8, message: "missing ')'".to_string() });
+        assert_eq!(error("old + new").message, "unknown name 'new', expected 'old' or a number");
+        assert_eq!(error("old ^ 2").position, 4);
+        assert_eq!(error("99999999999999999999").message, "99999999999999999999 is too big");
+    }
+
+    #[test]
+    fn test_display() {
+        for text in ["old * (old + 3)", "old - (2 - 1)", "(old + 1) % 7 * 2", "old * 2 + 3 / old", "old / (2 * 3)"] {
+            let expr = Expr::parse(text, "old").unwrap();
+            assert_eq!(expr.to_string(), text);
+        }
+        assert_eq!(Expr::parse("((old * 2)) + (3)", "old").unwrap().to_string(), "old * 2 + 3");
+    }
+
+    #[test]
+    fn test_predicates() {
+        let predicate = Predicate::parse("divisible by 23").unwrap();
+        assert_eq!(predicate, Predicate::DivisibleBy(23));
+        assert_eq!(predicate.evaluate(&46u64), Ok(true));
+
+        let predicate = Predicate::parse("new % 7 == 3").unwrap();
+        assert_eq!((predicate.evaluate(&10u64), predicate.evaluate(&11u64)), (Ok(true), Ok(false)));
+        assert_eq!(predicate.to_string(), "new % 7 == 3");
+
+        let predicate = Predicate::parse("new <= 1000").unwrap();
+        assert_eq!((predicate.evaluate(&1000u64), predicate.evaluate(&1001u64)), (Ok(true), Ok(false)));
+        assert!(Predicate::parse("divisible by 0").is_err());
+
+        assert_eq!(Predicate::parse("divisible by 23").unwrap().modulus(), Some(23));
+        assert_eq!(Predicate::parse("3 != (new * new + 1) % 7").unwrap().modulus(), Some(7));
+        assert_eq!(Predicate::parse("(new - 1) % 7 == 3").unwrap().modulus(), None);
+        assert_eq!(Predicate::parse("new % 7 < 3").unwrap().modulus(), None);
+        assert_eq!(Predicate::parse("new % 0 == 0").unwrap().modulus(), None);
+        assert!(Predicate::parse("new").is_err());
+        assert_eq!(Predicate::parse("new > old").unwrap_err().position, 6);
+    }
+}
@@ -2,79 +2,73 @@ use indoc::indoc;
 use regex::Regex;
 use std::collections::VecDeque;
 use std::str::FromStr;
-use std::{fs, io};
+use std::path::Path;
+use std::{env, fmt, fs, io};
 
-#[derive(Debug, Clone)]
-pub struct Error;
+use num_bigint::BigUint;
 
-#[derive(Debug, Clone)]
-pub enum Operation {
-    Mul(u64),
-    Add(u64),
-    Square,
-}
+mod cycles;
+mod expr;
+mod stats;
+mod worry;
 
-impl Operation {
-    fn evaluate(&self, old: &u64) -> u64 {
-        match self {
-            Self::Mul(i) => old * i,
-            Self::Add(i) => old + i,
-            Self::Square => old * old,
-        }
-    }
-}
+use expr::{EvalError, Expr, Predicate};
+use worry::{Normalize, Worry};
 
-impl FromStr for Operation {
-    type Err = Error;
+#[derive(Debug, Clone)]
+pub struct Error(pub String);
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<&str> = s.split(" ").collect();
-        if parts[0] == "*" && parts[1] == "old" {
-            Ok(Operation::Square)
-        } else if parts[0] == "*" {
-            Ok(Operation::Mul(parts[1].parse().unwrap()))
-        } else if parts[0] == "+" {
-            Ok(Operation::Add(parts[1].parse().unwrap()))
-        } else {
-            Err(Error {})
-        }
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Monkey {
+pub struct Monkey<W = u64> {
     pub id: u32,
-    pub items: VecDeque<u64>,
-    pub operation: Operation,
-    pub test_divisor: u64,
+    pub items: VecDeque<W>,
+    pub operation: Expr,
+    pub test: Predicate,
     pub test_true_dest: usize,
     pub test_false_dest: usize,
-    pub num_inspections: u32,
+    pub num_inspections: u64,
 }
 
-impl Monkey {
-    fn inspect_next<F>(&mut self, normalize: &F) -> Option<(u64, usize)>
-    where
-        F: Fn(u64) -> u64,
-    {
-        match self.items.pop_front() {
-            None => {
-                return None;
-            }
+impl<W: Worry> Monkey<W> {
+    /// Inspect the first item, giving its old and new worry levels and who to throw it to. If the
+    /// arithmetic fails, the item stays where it was.
+    fn inspect_next(&mut self, normalize: &Normalize) -> Result<Option<(W, W, usize)>, InspectError> {
+        match self.items.front() {
+            None => Ok(None),
             Some(item) => {
-                self.num_inspections += 1;
-                let mut new = self.operation.evaluate(&item);
-                new = normalize(new);
-                if &new % &self.test_divisor == 0 {
-                    return Some((new, self.test_true_dest));
-                } else {
-                    return Some((new, self.test_false_dest));
-                }
+                let count = self.num_inspections.checked_add(1).ok_or(InspectError::TooManyInspections)?;
+                let mut new = self.operation.evaluate(item)?;
+                new = normalize.apply(new);
+                let dest = if self.test.evaluate(&new)? { self.test_true_dest } else { self.test_false_dest };
+                let old = self.items.pop_front().unwrap();
+                self.num_inspections = count;
+                Ok(Some((old, new, dest)))
             }
         }
     }
 }
 
+impl Monkey {
+    /// The same monkey holding its items in another number type.
+    pub fn with_worry<V: Worry>(self) -> Monkey<V> {
+        Monkey {
+            id: self.id,
+            items: self.items.into_iter().map(V::from_u64).collect(),
+            operation: self.operation,
+            test: self.test,
+            test_true_dest: self.test_true_dest,
+            test_false_dest: self.test_false_dest,
+            num_inspections: self.num_inspections,
+        }
+    }
+}
+
 impl FromStr for Monkey {
     type Err = Error;
 
@@ -82,76 +76,418 @@ impl FromStr for Monkey {
         let re = indoc! {r#"
         Monkey (?P<id>\d+):
         \s*Starting items: (?P<items>[0-9, ]+)
-        \s*Operation: new = old (?P<operation>.+)
-        \s*Test: divisible by (?P<test_divisor>\d+)
+        \s*Operation: new = (?P<operation>.+)
+        \s*Test: (?P<test>.+)
         \s*If true: throw to monkey (?P<test_true_dest>\d+)
         \s*If false: throw to monkey (?P<test_false_dest>\d+)"#};
 
-        let monkey_regex = Regex::new(&re).unwrap();
+        let monkey_regex = Regex::new(re).unwrap();
 
-        let cap = monkey_regex.captures(s).unwrap();
+        let cap = monkey_regex.captures(s).ok_or_else(|| Error(format!("not a monkey: {:?}", s)))?;
         let id: u32 = cap["id"].parse().unwrap();
         let items: VecDeque<u64> = cap["items"]
             .split(", ")
             .map(|x| x.parse::<u64>().unwrap())
             .collect();
-        let operation: Operation = cap["operation"].parse().unwrap();
-        let test_divisor: u64 = cap["test_divisor"].parse().unwrap();
+        let operation = Expr::parse(&cap["operation"], "old")
+            .map_err(|e| Error(format!("monkey {} operation '{}': {}", id, &cap["operation"], e)))?;
+        let test = Predicate::parse(&cap["test"])
+            .map_err(|e| Error(format!("monkey {} test '{}': {}", id, &cap["test"], e)))?;
         let test_true_dest: usize = cap["test_true_dest"].parse().unwrap();
         let test_false_dest: usize = cap["test_false_dest"].parse().unwrap();
+        // A monkey can't throw to itself: the item would be back in its own queue to be inspected
+        // again straight away, maybe forever.
+        if [test_true_dest, test_false_dest].contains(&(id as usize)) {
+            return Err(Error(format!("monkey {} throws items to itself", id)));
+        }
+
+        Ok(Self { id, items, operation, test, test_true_dest, test_false_dest, num_inspections: 0 })
+    }
+}
+
+/// The smallest number worry levels can be kept modulo without changing where any item goes:
+/// the LCM of every test's modulus. That only works if every operation is made of `+` and `*`.
+pub fn reduction_modulus<W>(monkeys: &[Monkey<W>]) -> Result<u64, Error> {
+    let mut modulus = 1;
+    for monkey in monkeys {
+        if !monkey.operation.is_modular() {
+            return Err(Error(format!("monkey {}'s operation '{}' can't be worked out modulo anything", monkey.id, monkey.operation)));
+        }
+        let test_modulus = monkey.test.modulus()
+            .ok_or_else(|| Error(format!("monkey {}'s test '{}' can't be worked out modulo anything", monkey.id, monkey.test)))?;
+        modulus = worry::lcm(modulus, test_modulus)
+            .ok_or_else(|| Error(format!("the LCM of the tests up to monkey {} doesn't fit in 64 bits", monkey.id)))?;
+    }
+    Ok(modulus)
+}
+
+/// Why a monkey couldn't inspect an item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InspectError {
+    Eval(EvalError),
+    /// The monkey's inspection count doesn't fit in a `u64`.
+    TooManyInspections,
+}
+
+impl fmt::Display for InspectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InspectError::Eval(e) => e.fmt(f),
+            InspectError::TooManyInspections => write!(f, "too many inspections to count in 64 bits"),
+        }
+    }
+}
+
+impl From<EvalError> for InspectError {
+    fn from(e: EvalError) -> Self {
+        InspectError::Eval(e)
+    }
+}
+
+/// Arithmetic that went wrong part way through a game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundError {
+    pub round: u32,
+    pub monkey: u32,
+    /// The worry level of the item being inspected.
+    pub worry: String,
+    pub error: InspectError,
+}
 
-        Ok(Self { id, items, operation, test_divisor, test_true_dest, test_false_dest, num_inspections: 0 })
+impl fmt::Display for RoundError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "round {}, monkey {} inspecting an item with worry level {}: {}", self.round, self.monkey, self.worry, self.error)
     }
 }
 
-pub fn run_part<F>(part: u32, mut monkeys: Vec<Monkey>, cycles: u32, normalize: &F)
+impl std::error::Error for RoundError {}
+
+/// Something that happens during a game, for anyone watching.
+pub enum Event<'a, W> {
+    /// Monkey `monkey` inspected the item at the front of its queue, and threw it to `dest`.
+    Inspection { round: u32, monkey: usize, old: &'a W, new: &'a W, dest: usize },
+    /// Every monkey has had its turn.
+    RoundEnd { round: u32, monkeys: &'a [Monkey<W>] },
+}
+
+/// Play `cycles` rounds, counting inspections in each monkey's `num_inspections`.
+pub fn play<W: Worry>(monkeys: &mut [Monkey<W>], cycles: u32, normalize: &Normalize) -> Result<(), RoundError> {
+    play_with(monkeys, cycles, normalize, |_| {})
+}
+
+/// Play `cycles` rounds like [`play`], telling `observe` about every inspection and round.
+pub fn play_with<W, F>(monkeys: &mut [Monkey<W>], cycles: u32, normalize: &Normalize, mut observe: F) -> Result<(), RoundError>
 where
-    F: Fn(u64) -> u64,
+    W: Worry,
+    F: FnMut(Event<W>),
 {
-    for _ in 1..=cycles {
+    for round in 1..=cycles {
         for i in 0..monkeys.len() {
             loop {
-                let result: Option<(u64, usize)>;
+                let result: Option<(W, W, usize)>;
                 {
-                    result = monkeys[i].inspect_next(normalize);
+                    result = monkeys[i].inspect_next(normalize).map_err(|error| RoundError {
+                        round,
+                        monkey: monkeys[i].id,
+                        worry: monkeys[i].items[0].to_string(),
+                        error
+                    })?;
                 }
-                if let Some((item, dest)) = result {
+                if let Some((old, item, dest)) = result {
+                    observe(Event::Inspection { round, monkey: i, old: &old, new: &item, dest });
                     monkeys[dest].items.push_back(item);
                 } else {
                     break;
                 }
             }
         }
+        observe(Event::RoundEnd { round, monkeys });
     }
+    Ok(())
+}
 
-    let mut inspections: Vec<u32> = monkeys.iter().map(|m| m.num_inspections).collect();
+/// The two largest inspection counts multiplied.
+pub fn monkey_business(inspections: impl IntoIterator<Item = u64>) -> u128 {
+    let mut inspections: Vec<u64> = inspections.into_iter().collect();
     inspections.sort_by(|a, b| b.cmp(a));
-    println!(
-        "Answer for part {} is {}",
-        part,
-        inspections[0] as u128 * inspections[1] as u128
-    );
+    inspections[0] as u128 * inspections[1] as u128
+}
+
+/// Play `cycles` rounds and return the monkey business.
+pub fn run_part<W: Worry>(mut monkeys: Vec<Monkey<W>>, cycles: u32, normalize: &Normalize) -> Result<u128, RoundError> {
+    play(&mut monkeys, cycles, normalize)?;
+    Ok(monkey_business(monkeys.iter().map(|m| m.num_inspections)))
 }
 
-pub fn day11() {
-    let file = fs::read_to_string("input.txt").expect("File 'input.txt' not readable.");
-    let monkey_defs = file.split("\n\n").collect::<Vec<&str>>();
-    let mut monkeys: Vec<Monkey> = Vec::new();
-    for monkey_def in monkey_defs {
-        monkeys.push(monkey_def.parse().unwrap())
+/// Every monkey, numbered from 0 in order, and only throwing to monkeys that exist.
+pub fn parse_monkeys(text: &str) -> Result<Vec<Monkey>, Error> {
+    let monkeys: Vec<Monkey> = text.split("\n\n").map(|monkey_def| monkey_def.parse()).collect::<Result<_, _>>()?;
+    for (idx, monkey) in monkeys.iter().enumerate() {
+        if monkey.id as usize != idx {
+            return Err(Error(format!("expected monkey {}, found monkey {}", idx, monkey.id)));
+        }
+        if let Some(dest) = [monkey.test_true_dest, monkey.test_false_dest].into_iter().find(|&dest| dest >= monkeys.len()) {
+            return Err(Error(format!("monkey {} throws to monkey {}, but there are only {}", idx, dest, monkeys.len())));
+        }
     }
+    Ok(monkeys)
+}
 
-    // For part two, we need the LCM of the divisors (which are all prime)
-    let lcm: u64 = monkeys.iter().map(|x| x.test_divisor).product();
+pub fn read_monkeys(path: &str) -> Result<Vec<Monkey>, Error> {
+    let file = fs::read_to_string(path).map_err(|_| Error(format!("File '{}' not readable.", path)))?;
+    parse_monkeys(&file)
+}
+
+pub fn day11(monkeys: Vec<Monkey>) {
+    let part2 = reduction_modulus(&monkeys).map(Normalize::Reduce);
+
+    println!("{}", report(1, run_part(monkeys.clone(), 20, &Normalize::Relief(3))));
+    match part2 {
+        Ok(normalize) => println!("{}", report(2, run_part(monkeys, 10000, &normalize))),
+        Err(e) => println!("Part 2 can't be done: {}", e),
+    }
+}
+
+fn report(part: u32, answer: Result<u128, RoundError>) -> String {
+    match answer {
+        Ok(answer) => format!("Answer for part {} is {}", part, answer),
+        Err(e) => format!("Part {} failed on {}", part, e),
+    }
+}
+
+const USAGE: &str = "usage: day11
+       day11 --rounds N [--relief N | --reduce | --unreduced] [--big]
+       day11 --rounds N --cycles
+       day11 --stats DIR [--every N] [--rounds N] [--relief N | --reduce | --unreduced] [--big]";
+
+fn fail(message: impl fmt::Display) -> ! {
+    eprintln!("{}", message);
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
+/// A single game with chosen rules.
+struct Game {
+    rounds: u64,
+    normalize: Normalize,
+    /// Use arbitrary precision numbers rather than `u64`s.
+    big: bool,
+    /// Work out where each item goes from its cycle instead of playing every round.
+    cycles: bool,
+    /// Where to write per-round statistics and item journeys.
+    stats: Option<String>,
+    /// How often to print a row of the statistics table.
+    every: u32,
+}
 
-    run_part(1, monkeys.clone(), 20, &|x| x / 3);
-    run_part(2, monkeys, 10000, &|x| x % lcm);
+fn parse_game(args: &[String], monkeys: &[Monkey]) -> Result<Game, String> {
+    let mut game = Game { rounds: 20, normalize: Normalize::Relief(3), big: false, cycles: false, stats: None, every: 1 };
+    let mut idx = 0;
+    while idx < args.len() {
+        let number = |idx: usize| -> Result<u64, String> {
+            let value = args.get(idx + 1).ok_or(format!("{} needs a value", args[idx]))?;
+            value.parse().map_err(|_| format!("bad number '{}' for {}", value, args[idx]))
+        };
+        match args[idx].as_str() {
+            "--rounds" => { game.rounds = number(idx)?; idx += 1; },
+            "--relief" if number(idx)? == 0 => return Err("relief can't divide by zero".to_string()),
+            "--relief" => { game.normalize = Normalize::Relief(number(idx)?); idx += 1; },
+            "--reduce" => game.normalize = Normalize::Reduce(reduction_modulus(monkeys).map_err(|e| e.to_string())?),
+            "--unreduced" => game.normalize = Normalize::Nothing,
+            "--big" => game.big = true,
+            "--stats" => {
+                game.stats = Some(args.get(idx + 1).ok_or("--stats needs a directory")?.clone());
+                idx += 1;
+            },
+            "--every" => { game.every = number(idx)?.try_into().map_err(|_| "--every is too big".to_string())?; idx += 1; },
+            "--cycles" => {
+                game.normalize = Normalize::Reduce(reduction_modulus(monkeys).map_err(|e| e.to_string())?);
+                game.cycles = true;
+            },
+            flag => return Err(format!("unexpected '{}'", flag))
+        }
+        idx += 1;
+    }
+    if game.cycles && (game.big || args.iter().any(|arg| arg.starts_with("--relief") || arg == "--unreduced")) {
+        return Err("--cycles always reduces worry levels with u64s".to_string());
+    }
+    if game.cycles && game.stats.is_some() {
+        return Err("--stats needs every round to be played".to_string());
+    }
+    if !game.cycles && game.rounds > u32::MAX as u64 {
+        return Err(format!("{} rounds is too many to play one at a time; try --cycles", game.rounds));
+    }
+    Ok(game)
+}
+
+/// Play a game while recording it, then write `rounds.csv`, `items.csv` and `stats.json` into
+/// `dir` and print a table of the rounds. Whatever was recorded gets written even if the game
+/// goes wrong part way through.
+fn record_game<W: Worry>(mut monkeys: Vec<Monkey<W>>, game: &Game, dir: &Path) -> io::Result<Result<u128, RoundError>> {
+    let mut recorder = stats::Stats::new(&monkeys);
+    let result = play_with(&mut monkeys, game.rounds as u32, &game.normalize, |event| recorder.record(event));
+
+    fs::create_dir_all(dir)?;
+    recorder.write_rounds_csv(&mut io::BufWriter::new(fs::File::create(dir.join("rounds.csv"))?))?;
+    recorder.write_items_csv(&mut io::BufWriter::new(fs::File::create(dir.join("items.csv"))?))?;
+    recorder.write_json(&mut io::BufWriter::new(fs::File::create(dir.join("stats.json"))?))?;
+    recorder.write_table(&mut io::stdout().lock(), game.every)?;
+    Ok(result.map(|_| monkey_business(monkeys.iter().map(|m| m.num_inspections))))
 }
 
 pub fn main() -> io::Result<()> {
-    day11();
+    let monkeys = read_monkeys("input.txt").unwrap_or_else(|e| fail(e));
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() {
+        day11(monkeys);
+        return Ok(());
+    }
+
+    let game = parse_game(&args, &monkeys).unwrap_or_else(|e| fail(e));
+    let answer = match (&game.stats, game.normalize) {
+        (Some(dir), _) if game.big =>
+            record_game(monkeys.into_iter().map(Monkey::with_worry::<BigUint>).collect(), &game, Path::new(dir))?,
+        (Some(dir), _) => record_game(monkeys, &game, Path::new(dir))?,
+        (None, Normalize::Reduce(modulus)) if game.cycles =>
+            return finish(&game, cycles::inspections_after(&monkeys, modulus, game.rounds).map(monkey_business)),
+        _ if game.big =>
+            run_part(monkeys.into_iter().map(Monkey::with_worry::<BigUint>).collect(), game.rounds as u32, &game.normalize),
+        _ => run_part(monkeys, game.rounds as u32, &game.normalize),
+    };
+    finish(&game, answer)
+}
+
+fn finish(game: &Game, answer: Result<u128, impl fmt::Display>) -> io::Result<()> {
+    match answer {
+        Ok(answer) => println!("Monkey business after {} rounds is {}", game.rounds, answer),
+        Err(e) => {
+            eprintln!("Failed on {}", e);
+            std::process::exit(1);
+        }
+    }
     Ok(())
 }
 
+#[cfg(test)]
+ mod test {
+    use super::*;
+    use expr::BinOp;
+
+    pub const SAMPLE: &str = indoc! {"
+        Monkey 0:
+          Starting items: 79, 98
+          Operation: new = old * 19
+          Test: divisible by 23
+            If true: throw to monkey 2
+            If false: throw to monkey 3
+
+        Monkey 1:
+          Starting items: 54, 65, 75, 74
+          Operation: new = old + 6
+          Test: divisible by 19
+            If true: throw to monkey 2
+            If false: throw to monkey 0
+
+        Monkey 2:
+          Starting items: 79, 60, 97
+          Operation: new = old * old
+          Test: divisible by 13
+            If true: throw to monkey 1
+            If false: throw to monkey 3
+
+        Monkey 3:
+          Starting items: 74
+          Operation: new = old + 3
+          Test: divisible by 17
+            If true: throw to monkey 0
+            If false: throw to monkey 1"};
+
+    fn parse(input: &str) -> Vec<Monkey> {
+        parse_monkeys(input).unwrap()
+    }
+
+    #[test]
+    fn test_bad_throws() {
+        let input = SAMPLE.replace("If true: throw to monkey 2", "If true: throw to monkey 0");
+        assert_eq!(parse_monkeys(&input).unwrap_err().to_string(), "monkey 0 throws items to itself");
+        let input = SAMPLE.replace("If false: throw to monkey 3\n\nMonkey 1", "If false: throw to monkey 4\n\nMonkey 1");
+        assert_eq!(parse_monkeys(&input).unwrap_err().to_string(), "monkey 0 throws to monkey 4, but there are only 4");
+        let input = SAMPLE.replace("Monkey 1:", "Monkey 5:");
+        assert_eq!(parse_monkeys(&input).unwrap_err().to_string(), "expected monkey 1, found monkey 5");
+    }
+
+    #[test]
+    fn test_sample() {
+        let monkeys = parse(SAMPLE);
+        assert_eq!(monkeys[2].operation.to_string(), "old * old");
+        assert_eq!(run_part(monkeys.clone(), 20, &Normalize::Relief(3)).unwrap(), 10605);
+        assert_eq!(reduction_modulus(&monkeys).unwrap(), 23 * 19 * 13 * 17);
+        assert_eq!(run_part(monkeys, 10000, &Normalize::Reduce(23 * 19 * 13 * 17)).unwrap(), 2713310158);
+    }
+
+    #[test]
+    fn test_general_monkeys() {
+        let input = SAMPLE
+            .replace("new = old + 6", "new = (old + old) * 2 - 1")
+            .replace("Test: divisible by 17", "Test: new % 5 != 0");
+        let monkeys = parse(&input);
+        assert_eq!(monkeys[3].test.to_string(), "new % 5 != 0");
+
+        // Item 54 goes to monkey 1's (54 + 54) * 2 - 1 = 215, /3 to 71, which isn't divisible by 19.
+        let mut monkey = monkeys[1].clone();
+        assert_eq!(monkey.inspect_next(&Normalize::Relief(3)), Ok(Some((54, 71, 0))));
+
+        let error = "Monkey 0:\n Starting items: 1\n Operation: new = old ** 2\n Test: divisible by 2\n If true: throw to monkey 1\n If false: throw to monkey 1";
+        assert_eq!(error.parse::<Monkey>().unwrap_err().to_string(), "monkey 0 operation 'old ** 2': unexpected '*' at column 6");
+    }
+
+    #[test]
+    fn test_reduction_modulus() {
+        // Divisors that share factors: the product, 15300, would work but is bigger than it needs to be.
+        let input = SAMPLE.replace("by 23", "by 6").replace("by 19", "by 10").replace("by 13", "by 15");
+        let monkeys = parse(&input);
+        assert_eq!(reduction_modulus(&monkeys).unwrap(), 510);
+
+        // Keeping worry levels modulo the LCM throws every item the same way as never reducing them.
+        let big: Vec<Monkey<BigUint>> = monkeys.iter().cloned().map(Monkey::with_worry).collect();
+        assert_eq!(
+            run_part(monkeys.clone(), 12, &Normalize::Reduce(510)),
+            run_part(big, 12, &Normalize::Nothing)
+        );
+
+        let input = SAMPLE.replace("Test: divisible by 17", "Test: 2 == new % 3 * 2 % 4");
+        assert!(reduction_modulus(&parse(&input)).is_err());
+        let input = SAMPLE.replace("Test: divisible by 17", "Test: (new + 1) % 4 == 2");
+        assert_eq!(reduction_modulus(&parse(&input)).unwrap(), 23 * 19 * 13 * 4);
+        let input = SAMPLE.replace("new = old + 3", "new = old - 3");
+        assert_eq!(reduction_modulus(&parse(&input)).unwrap_err().to_string(), "monkey 3's operation 'old - 3' can't be worked out modulo anything");
+    }
+
+    #[test]
+    fn test_overflow() {
+        // Without any reduction the squaring soon runs out of room.
+        let error = run_part(parse(SAMPLE), 1000, &Normalize::Nothing).unwrap_err();
+        assert_eq!((error.round, error.monkey), (13, 0));
+        assert_eq!(error.error, InspectError::Eval(EvalError::Overflow { op: BinOp::Mul, lhs: 13988703546165100909, rhs: 19 }));
+        assert_eq!(
+            error.to_string(),
+            "round 13, monkey 0 inspecting an item with worry level 13988703546165100909: 13988703546165100909 * 19 is out of range"
+        );
+
+        // The item that did it is still with the monkey, and wasn't counted as inspected.
+        let mut monkeys = parse(SAMPLE);
+        monkeys[0].items = VecDeque::from([u64::MAX / 2]);
+        assert!(monkeys[0].inspect_next(&Normalize::Nothing).is_err());
+        assert_eq!((monkeys[0].items.len(), monkeys[0].num_inspections), (1, 0));
+
+        // So is one that would take the count past what a u64 holds.
+        let mut monkeys = parse(SAMPLE);
+        monkeys[0].num_inspections = u64::MAX;
+        let error = play(&mut monkeys, 1, &Normalize::Relief(3)).unwrap_err();
+        assert_eq!((error.round, error.monkey, error.error), (1, 0, InspectError::TooManyInspections));
+        assert_eq!((monkeys[0].items.len(), monkeys[0].num_inspections), (2, u64::MAX));
+    }
+}
+
 // Answer for part 1 is 55216
 // Answer for part 2 is 12848882750
@@ -0,0 +1,232 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
+use crate::{Event, Monkey};
+use crate::worry::Worry;
+
+/// Every monkey's queue and inspection count at the end of a round (or at the start, for round 0).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub round: u32,
+    /// The worry levels each monkey holds, front of the queue first.
+    pub queues: Vec<Vec<String>>,
+    pub inspections: Vec<u64>,
+}
+
+/// One inspection of an item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Visit {
+    pub round: u32,
+    pub monkey: usize,
+    pub old: String,
+    pub new: String,
+    pub dest: usize,
+}
+
+/// Where an item went, numbered in the order the items are listed in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Journey {
+    pub item: usize,
+    pub start_monkey: usize,
+    pub start_worry: String,
+    pub visits: Vec<Visit>,
+}
+
+/// Everything that happened in a game, gathered from its [`Event`]s.
+///
+/// Worry levels are kept as decimal strings so games played with big numbers can be recorded too.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub snapshots: Vec<Snapshot>,
+    pub journeys: Vec<Journey>,
+    /// Which item is where in each monkey's queue. Queues are first in, first out, so following
+    /// the throws is enough to know which item each inspection was of.
+    ids: Vec<VecDeque<usize>>,
+}
+
+fn snapshot<W: Worry>(round: u32, monkeys: &[Monkey<W>]) -> Snapshot {
+    Snapshot {
+        round,
+        queues: monkeys.iter().map(|m| m.items.iter().map(|w| w.to_string()).collect()).collect(),
+        inspections: monkeys.iter().map(|m| m.num_inspections).collect(),
+    }
+}
+
+impl Stats {
+    /// Start recording a game that's about to begin.
+    pub fn new<W: Worry>(monkeys: &[Monkey<W>]) -> Self {
+        let mut stats = Stats { snapshots: vec![snapshot(0, monkeys)], ..Default::default() };
+        for (holder, monkey) in monkeys.iter().enumerate() {
+            let mut ids = VecDeque::new();
+            for worry in &monkey.items {
+                ids.push_back(stats.journeys.len());
+                stats.journeys.push(Journey { item: stats.journeys.len(), start_monkey: holder, start_worry: worry.to_string(), visits: Vec::new() });
+            }
+            stats.ids.push(ids);
+        }
+        stats
+    }
+
+    pub fn record<W: Worry>(&mut self, event: Event<W>) {
+        match event {
+            Event::Inspection { round, monkey, old, new, dest } => {
+                let item = self.ids[monkey].pop_front().expect("an inspected item was in the queue");
+                self.ids[dest].push_back(item);
+                self.journeys[item].visits.push(Visit { round, monkey, old: old.to_string(), new: new.to_string(), dest });
+            },
+            Event::RoundEnd { round, monkeys } => self.snapshots.push(snapshot(round, monkeys)),
+        }
+    }
+
+    /// One row per monkey per round: `round,monkey,inspections,held,queue`, with the queue's worry
+    /// levels separated by spaces.
+    pub fn write_rounds_csv(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "round,monkey,inspections,held,queue")?;
+        for snapshot in &self.snapshots {
+            for (monkey, (queue, inspections)) in snapshot.queues.iter().zip(&snapshot.inspections).enumerate() {
+                writeln!(out, "{},{},{},{},{}", snapshot.round, monkey, inspections, queue.len(), queue.join(" "))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// One row per inspection, grouped by item: `item,round,monkey,old,new,dest`.
+    pub fn write_items_csv(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "item,round,monkey,old,new,dest")?;
+        for journey in &self.journeys {
+            for visit in &journey.visits {
+                writeln!(out, "{},{},{},{},{},{}", journey.item, visit.round, visit.monkey, visit.old, visit.new, visit.dest)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The snapshots and journeys as one JSON object. Everything in it is a number or a list of
+    /// numbers, so it's written out by hand.
+    pub fn write_json(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "{{\n  \"rounds\": [")?;
+        for (idx, snapshot) in self.snapshots.iter().enumerate() {
+            let monkeys: Vec<String> = snapshot.queues.iter().zip(&snapshot.inspections)
+                .map(|(queue, inspections)| format!("{{\"inspections\": {}, \"queue\": [{}]}}", inspections, queue.join(", ")))
+                .collect();
+            let comma = if idx + 1 < self.snapshots.len() { "," } else { "" };
+            writeln!(out, "    {{\"round\": {}, \"monkeys\": [{}]}}{}", snapshot.round, monkeys.join(", "), comma)?;
+        }
+        writeln!(out, "  ],\n  \"items\": [")?;
+        for (idx, journey) in self.journeys.iter().enumerate() {
+            let visits: Vec<String> = journey.visits.iter()
+                .map(|v| format!("{{\"round\": {}, \"monkey\": {}, \"old\": {}, \"new\": {}, \"dest\": {}}}", v.round, v.monkey, v.old, v.new, v.dest))
+                .collect();
+            let comma = if idx + 1 < self.journeys.len() { "," } else { "" };
+            writeln!(out, "    {{\"item\": {}, \"start_monkey\": {}, \"start_worry\": {}, \"visits\": [{}]}}{}",
+                journey.item, journey.start_monkey, journey.start_worry, visits.join(", "), comma)?;
+        }
+        writeln!(out, "  ]\n}}")
+    }
+
+    /// Inspection counts and how many items each monkey holds, every `every` rounds and after
+    /// the last one.
+    pub fn write_table(&self, out: &mut impl Write, every: u32) -> io::Result<()> {
+        let num_monkeys = self.snapshots.first().map_or(0, |s| s.queues.len());
+        let last = self.snapshots.last().map_or(0, |s| s.round);
+        let width = self.snapshots.iter()
+            .flat_map(|s| s.inspections.iter().zip(&s.queues).map(|(i, q)| format!("{} ({})", i, q.len()).len()))
+            .chain((0..num_monkeys).map(|m| format!("monkey {}", m).len()))
+            .max().unwrap_or(0);
+
+        write!(out, "{:>7}", "round")?;
+        for monkey in 0..num_monkeys {
+            write!(out, " | {:>width$}", format!("monkey {}", monkey))?;
+        }
+        writeln!(out)?;
+        writeln!(out, "{}", "-".repeat(7 + num_monkeys * (width + 3)))?;
+        for snapshot in self.snapshots.iter().filter(|s| s.round.is_multiple_of(every.max(1)) || s.round == last) {
+            write!(out, "{:>7}", snapshot.round)?;
+            for (inspections, queue) in snapshot.inspections.iter().zip(&snapshot.queues) {
+                write!(out, " | {:>width$}", format!("{} ({})", inspections, queue.len()))?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+    use crate::play_with;
+    use crate::worry::Normalize;
+
+    fn recorded(rounds: u32) -> Stats {
+        let mut monkeys: Vec<Monkey> = crate::test::SAMPLE.split("\n\n").map(|def| def.parse().unwrap()).collect();
+        let mut stats = Stats::new(&monkeys);
+        play_with(&mut monkeys, rounds, &Normalize::Relief(3), |event| stats.record(event)).unwrap();
+        stats
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let stats = recorded(20);
+        assert_eq!(stats.snapshots.len(), 21);
+        // From the puzzle's description of the first round.
+        assert_eq!(stats.snapshots[1].queues, vec![
+            vec!["20", "23", "27", "26"],
+            vec!["2080", "25", "167", "207", "401", "1046"],
+            vec![],
+            vec![],
+        ]);
+        assert_eq!(stats.snapshots[20].inspections, vec![101, 95, 7, 105]);
+    }
+
+    #[test]
+    fn test_journeys() {
+        let stats = recorded(1);
+        assert_eq!(stats.journeys.len(), 10);
+        // Monkey 2's first item, 79, is squared to 6241, /3 to 2080, which is divisible by 13, so
+        // it goes to monkey 1, who has already had its turn.
+        let journey = &stats.journeys[6];
+        assert_eq!((journey.start_monkey, journey.start_worry.as_str()), (2, "79"));
+        let steps: Vec<(usize, &str, usize)> = journey.visits.iter().map(|v| (v.monkey, v.new.as_str(), v.dest)).collect();
+        assert_eq!(steps, vec![(2, "2080", 1)]);
+
+        // Every inspection is some item's visit.
+        let recorded: u64 = stats.journeys.iter().map(|j| j.visits.len() as u64).sum();
+        assert_eq!(recorded, stats.snapshots[1].inspections.iter().sum());
+    }
+
+    #[test]
+    fn test_exports() {
+        let stats = recorded(1);
+        let mut csv = Vec::new();
+        stats.write_rounds_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().nth(1), Some("0,0,0,2,79 98"));
+        assert_eq!(csv.lines().nth(6), Some("1,1,4,6,2080 25 167 207 401 1046"));
+
+        let mut csv = Vec::new();
+        stats.write_items_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().nth(1), Some("0,1,0,79,500,3"));
+
+        let mut json = Vec::new();
+        stats.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.contains("{\"round\": 0, \"monkeys\": [{\"inspections\": 0, \"queue\": [79, 98]}, "));
+        assert!(json.contains(concat!(
+            "{\"item\": 0, \"start_monkey\": 0, \"start_worry\": 79, \"visits\": [",
+            "{\"round\": 1, \"monkey\": 0, \"old\": 79, \"new\": 500, \"dest\": 3}, ",
+            "{\"round\": 1, \"monkey\": 3, \"old\": 500, \"new\": 167, \"dest\": 1}]},"
+        )));
+        assert!(json.trim_end().ends_with("]\n}"));
+
+        let mut table = Vec::new();
+        recorded(20).write_table(&mut table, 10).unwrap();
+        assert_eq!(String::from_utf8(table).unwrap(), concat!(
+            "  round | monkey 0 | monkey 1 | monkey 2 | monkey 3\n",
+            "---------------------------------------------------\n",
+            "      0 |    0 (2) |    0 (4) |    0 (3) |    0 (1)\n",
+            "     10 |   48 (4) |   48 (6) |    6 (0) |   52 (0)\n",
+            "     20 |  101 (5) |   95 (5) |    7 (0) |  105 (0)\n",
+        ));
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt;
+
+use num_bigint::BigUint;
+
+use crate::expr::{BinOp, EvalError};
+
+/// A number that can hold a worry level: a `u64` that reports overflow, or a `BigUint` that
+/// never runs out of room.
+pub trait Worry: Clone + Ord + fmt::Display + fmt::Debug {
+    fn from_u64(value: u64) -> Self;
+
+    fn apply(op: BinOp, lhs: &Self, rhs: &Self) -> Result<Self, EvalError>;
+
+    /// The remainder after dividing by `divisor`, which mustn't be zero.
+    fn rem_u64(&self, divisor: u64) -> u64;
+
+    fn div_u64(&self, divisor: u64) -> Self;
+}
+
+impl Worry for u64 {
+    fn from_u64(value: u64) -> Self {
+        value
+    }
+
+    fn apply(op: BinOp, &lhs: &Self, &rhs: &Self) -> Result<Self, EvalError> {
+        let result = match op {
+            BinOp::Add => lhs.checked_add(rhs),
+            BinOp::Sub => lhs.checked_sub(rhs),
+            BinOp::Mul => lhs.checked_mul(rhs),
+            BinOp::Div | BinOp::Rem if rhs == 0 => return Err(EvalError::DivisionByZero),
+            BinOp::Div => lhs.checked_div(rhs),
+            BinOp::Rem => lhs.checked_rem(rhs),
+        };
+        result.ok_or(EvalError::Overflow { op, lhs, rhs })
+    }
+
+    fn rem_u64(&self, divisor: u64) -> u64 {
+        self % divisor
+    }
+
+    fn div_u64(&self, divisor: u64) -> Self {
+        self / divisor
+    }
+}
+
+impl Worry for BigUint {
+    fn from_u64(value: u64) -> Self {
+        BigUint::from(value)
+    }
+
+    fn apply(op: BinOp, lhs: &Self, rhs: &Self) -> Result<Self, EvalError> {
+        match op {
+            BinOp::Add => Ok(lhs + rhs),
+            BinOp::Sub if lhs < rhs => Err(EvalError::Negative),
+            BinOp::Sub => Ok(lhs - rhs),
+            BinOp::Mul => Ok(lhs * rhs),
+            BinOp::Div | BinOp::Rem if *rhs == BigUint::ZERO => Err(EvalError::DivisionByZero),
+            BinOp::Div => Ok(lhs / rhs),
+            BinOp::Rem => Ok(lhs % rhs),
+        }
+    }
+
+    fn rem_u64(&self, divisor: u64) -> u64 {
+        u64::try_from(self % divisor).expect("a remainder is smaller than its divisor")
+    }
+
+    fn div_u64(&self, divisor: u64) -> Self {
+        self / divisor
+    }
+}
+
+/// What happens to a worry level after the monkey's operation, before its test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalize {
+    /// Relief that the item wasn't damaged divides the worry by this much (part 1 uses 3).
+    Relief(u64),
+    /// Keep the worry modulo this, which has to be a multiple of every test's modulus.
+    Reduce(u64),
+    /// Let worry levels grow as far as the number type allows.
+    Nothing,
+}
+
+impl Normalize {
+    pub fn apply<W: Worry>(&self, worry: W) -> W {
+        match *self {
+            Normalize::Relief(divisor) => worry.div_u64(divisor),
+            Normalize::Reduce(modulus) => W::from_u64(worry.rem_u64(modulus)),
+            Normalize::Nothing => worry,
+        }
+    }
+}
+
+pub fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// The lowest common multiple, or `None` if it doesn't fit in a `u64`.
+pub fn lcm(a: u64, b: u64) -> Option<u64> {
+    if a == 0 || b == 0 {
+        return Some(0);
+    }
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+
+    #[test]
+    fn test_lcm() {
+        assert_eq!(gcd(12, 18), 6);
+        assert_eq!(lcm(4, 6), Some(12));
+        assert_eq!(lcm(23, 19), Some(437));
+        assert_eq!(lcm(1 << 40, 3 << 30), Some(3 << 40));
+        assert_eq!(lcm(u64::MAX, u64::MAX - 1), None);
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(Normalize::Relief(3).apply(500u64), 166);
+        assert_eq!(Normalize::Reduce(96577).apply(BigUint::from(u64::MAX) * 7u8), BigUint::from((u64::MAX as u128 * 7 % 96577) as u64));
+        assert_eq!(Normalize::Nothing.apply(5u64), 5);
+        assert_eq!(u64::apply(BinOp::Add, &u64::MAX, &1), Err(EvalError::Overflow { op: BinOp::Add, lhs: u64::MAX, rhs: 1 }));
+    }
+}
@@ -1,6 +1,11 @@
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
 use std::str::FromStr;
 use std::{fs, io};
 
+mod render;
+
 #[derive(Debug, Clone)]
 pub struct Error;
 
@@ -12,6 +17,9 @@ pub struct PathSolver {
 
     // For calculations
     cost_to_end : Vec<Vec<Option<i32>>>,
+    // The search runs backwards from the end, so the square each one was reached from is its
+    // next step along a shortest route to the end.
+    toward_end : Vec<Vec<Option<(usize, usize)>>>,
 }
 
 impl PathSolver {
@@ -47,11 +55,41 @@ impl PathSolver {
                 for (nx, ny) in self.neighbors(cx, cy) {
                     if self.cost_to_end[ny][nx].is_some() { continue; }
                     self.cost_to_end[ny][nx] = Some(self.cost_to_end[cy][cx].unwrap() + 1);
+                    self.toward_end[ny][nx] = Some((cx, cy));
                     candidates.push((nx, ny));
                 }
             }
         }
     }
+
+    /// A shortest route from `start` to the end, both included, or `None` if the end can't be
+    /// reached. Call `compute_cost_to_end` first.
+    pub fn path_from(&self, start: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        let (sx, sy) = start;
+        self.cost_to_end.get(sy)?.get(sx)?.as_ref()?;
+        let mut path = vec![start];
+        let mut at = start;
+        while let Some(next) = self.toward_end[at.1][at.0] {
+            path.push(next);
+            at = next;
+        }
+        Some(path)
+    }
+
+    /// The lowest square with the fewest steps to the end, if any can reach it.
+    pub fn best_lowest_start(&self) -> Option<(usize, usize)> {
+        let mut best = None;
+        for (y, (height_row, step_row)) in self.height.iter().zip(&self.cost_to_end).enumerate() {
+            for (x, (height, steps)) in height_row.iter().zip(step_row).enumerate() {
+                if let (0, Some(steps)) = (*height, *steps) {
+                    if best.is_none_or(|(_, best_steps)| steps < best_steps) {
+                        best = Some(((x, y), steps));
+                    }
+                }
+            }
+        }
+        best.map(|(start, _)| start)
+    }
 }
 
 impl FromStr for PathSolver {
@@ -63,15 +101,16 @@ impl FromStr for PathSolver {
 
         let mut height : Vec<Vec<i32>> = Vec::new();
         let mut steps : Vec<Vec<Option<i32>>> = Vec::new();
+        let mut toward_end = Vec::new();
         for (y, line) in s.lines().enumerate() {
             let mut height_row : Vec<i32> = Vec::new();
             let mut steps_row : Vec<Option<i32>> = Vec::new();
             for (x, byte) in line.bytes().enumerate() {
-                if byte == 'S' as u8 {
+                if byte == b'S' {
                     start = (x, y);
                     height_row.push(0);
                     steps_row.push( None );
-                } else if byte == 'E' as u8 {
+                } else if byte == b'E' {
                     end = (x, y);
                     height_row.push(25);
                     steps_row.push( Some(0) );
@@ -80,11 +119,12 @@ impl FromStr for PathSolver {
                     steps_row.push( None );
                 }
             }
+            toward_end.push( vec![None; height_row.len()] );
             height.push( height_row );
             steps.push( steps_row );
         }
 
-        Ok(Self { start, end, height, cost_to_end:steps })
+        Ok(Self { start, end, height, cost_to_end:steps, toward_end })
     }
 }
 
@@ -101,8 +141,8 @@ pub fn day12() {
 
     for (height_row, step_row) in solver.height.iter().zip(solver.cost_to_end) {
         for (height, steps) in height_row.iter().zip(step_row) {
-            if *height == 0 && steps.is_some() && steps.unwrap() < min_steps {
-                min_steps = steps.unwrap();
+            if let (0, Some(steps)) = (*height, steps) {
+                min_steps = min_steps.min(steps);
             }
         }
     }
@@ -110,8 +150,56 @@ pub fn day12() {
     println!("Min Steps: {}", min_steps);
 }
 
+/// The route from `S`, or with `lowest` from the best of the lowest squares.
+fn route(lowest: bool) -> (PathSolver, Vec<(usize, usize)>) {
+    let file = fs::read_to_string("input.txt").expect("File 'input.txt' not readable.");
+    let mut solver : PathSolver = file.parse().unwrap();
+    solver.compute_cost_to_end();
+    let start = if lowest { solver.best_lowest_start() } else { Some(solver.start) };
+    match start.and_then(|start| solver.path_from(start)) {
+        Some(path) => (solver, path),
+        None => {
+            eprintln!("There's no way to the end.");
+            std::process::exit(1);
+        }
+    }
+}
+
+const USAGE: &str = "usage: day12
+       day12 --arrows [--lowest]
+       day12 --png FILE [SCALE] [--lowest]";
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
 pub fn main() -> io::Result<()> {
-    day12();
+    let mut args : Vec<String> = env::args().skip(1).collect();
+    let lowest = match args.iter().position(|arg| arg == "--lowest") {
+        Some(idx) => { args.remove(idx); true },
+        None => false,
+    };
+    let args : Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+    match args[..] {
+        [] if !lowest => day12(),
+        ["--arrows"] => {
+            let (solver, path) = route(lowest);
+            print!("{}", render::arrows(solver.height[0].len(), solver.height.len(), &path));
+            println!("{} steps", path.len() - 1);
+        },
+        ["--png", file] | ["--png", file, _] => {
+            let scale = match args.get(2) {
+                Some(scale) => scale.parse().unwrap_or_else(|_| fail(format!("bad scale '{}'", scale))),
+                None => 6,
+            };
+            let (solver, path) = route(lowest);
+            render::Terrain::new(&solver.height, &path).write_png(BufWriter::new(File::create(file)?), scale)?;
+            println!("Wrote {}", file);
+        },
+        _ => fail("unexpected arguments"),
+    }
     Ok(())
 }
 
@@ -134,4 +222,37 @@ pub fn main() -> io::Result<()> {
         let (sx, sy) = solver.start;
         assert_eq!(solver.cost_to_end[sy][sx], Some(31));
     }
+
+    #[test]
+    fn test_path_from() {
+        let input = indoc!{"
+            Sabqponm
+            abcryxxl
+            accszExk
+            acctuvwj
+            abdefghi
+        "};
+        let mut solver : PathSolver = input.parse().unwrap();
+        solver.compute_cost_to_end();
+
+        let path = solver.path_from(solver.start).unwrap();
+        assert_eq!(path.len(), 32);
+        assert_eq!((path[0], path[31]), (solver.start, solver.end));
+        for step in path.windows(2) {
+            let ((x0, y0), (x1, y1)) = (step[0], step[1]);
+            assert_eq!(x0.abs_diff(x1) + y0.abs_diff(y1), 1);
+            assert!(solver.height[y1][x1] - solver.height[y0][x0] <= 1);
+        }
+
+        let lowest = solver.best_lowest_start().unwrap();
+        assert_eq!(solver.path_from(lowest).unwrap().len(), 30);
+        assert_eq!(solver.path_from(solver.end), Some(vec![solver.end]));
+        assert_eq!(solver.path_from((99, 0)), None);
+
+        // A wall of 'z's with no way up to it.
+        let mut solver : PathSolver = "Sz\nzE\n".parse().unwrap();
+        solver.compute_cost_to_end();
+        assert_eq!(solver.path_from(solver.start), None);
+        assert_eq!(solver.best_lowest_start(), None);
+    }
 }
@@ -0,0 +1,176 @@
+use std::io::{self, Write};
+
+pub type Rgb = (u8, u8, u8);
+
+/// Stops along the terrain scale from `a` to `z`: lowland green, through sand and brown rock,
+/// to snow.
+const TERRAIN: [Rgb; 4] = [(40, 110, 50), (200, 180, 110), (120, 85, 60), (245, 245, 250)];
+const ROUTE: Rgb = (220, 30, 30);
+const START: Rgb = (30, 60, 220);
+const END: Rgb = (255, 200, 0);
+
+/// The route drawn like the puzzle statement: an arrow on each square pointing to the next one,
+/// `E` at the end and `.` everywhere else.
+pub fn arrows(width: usize, height: usize, path: &[(usize, usize)]) -> String {
+    let mut grid = vec![vec!['.'; width]; height];
+    for step in path.windows(2) {
+        let ((x0, y0), (x1, y1)) = (step[0], step[1]);
+        grid[y0][x0] = match (x1 as isize - x0 as isize, y1 as isize - y0 as isize) {
+            (1, 0) => '>',
+            (-1, 0) => '<',
+            (0, 1) => 'v',
+            (0, -1) => '^',
+            _ => panic!("({}, {}) to ({}, {}) isn't one step", x0, y0, x1, y1),
+        };
+    }
+    if let Some(&(x, y)) = path.last() {
+        grid[y][x] = 'E';
+    }
+    grid.into_iter().map(|row| row.into_iter().collect::<String>() + "\n").collect()
+}
+
+/// The colour for a height from 0 to 25, blending between neighbouring stops.
+pub fn terrain(height: i32) -> Rgb {
+    let position = height.clamp(0, 25) as f64 / 25.0 * (TERRAIN.len() - 1) as f64;
+    let idx = (position.floor() as usize).min(TERRAIN.len() - 2);
+    let frac = position - idx as f64;
+    let blend = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * frac).round() as u8;
+    let (low, high) = (TERRAIN[idx], TERRAIN[idx + 1]);
+    (blend(low.0, high.0), blend(low.1, high.1), blend(low.2, high.2))
+}
+
+/// Lighten (positive `amount`) or darken a colour.
+fn shade((r, g, b): Rgb, amount: f64) -> Rgb {
+    let towards = |c: u8| if amount >= 0.0 {
+        c as f64 + (255.0 - c as f64) * amount
+    } else {
+        c as f64 * (1.0 + amount)
+    };
+    let channel = |c: u8| towards(c).round().clamp(0.0, 255.0) as u8;
+    (channel(r), channel(g), channel(b))
+}
+
+/// A height map with a route over it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Terrain<'a> {
+    height : &'a [Vec<i32>],
+    path : &'a [(usize, usize)],
+}
+
+impl<'a> Terrain<'a> {
+    pub fn new(height: &'a [Vec<i32>], path: &'a [(usize, usize)]) -> Self {
+        Self { height, path }
+    }
+
+    fn rows(&self) -> usize {
+        self.height.len()
+    }
+
+    fn cols(&self) -> usize {
+        self.height.first().map_or(0, |row| row.len())
+    }
+
+    /// The colour of a square, lit from the top left: slopes facing the light are lighter and
+    /// those facing away darker, so climbs stand out from flat ground of the same height.
+    fn ground(&self, x: usize, y: usize) -> Rgb {
+        let here = self.height[y][x];
+        let behind = self.height[y.saturating_sub(1)][x.saturating_sub(1)];
+        shade(terrain(here), ((here - behind) as f64 * 0.15).clamp(-0.45, 0.45))
+    }
+
+    /// RGB rows of pixels, `scale` pixels to a square, with the route as a line joining the
+    /// middles of its squares and its ends marked.
+    pub fn pixels(&self, scale: usize) -> (usize, usize, Vec<u8>) {
+        let scale = scale.max(1);
+        let (width, height) = (self.cols() * scale, self.rows() * scale);
+        let mut pixels = vec![0; width * height * 3];
+        let mut put = |x: usize, y: usize, (r, g, b): Rgb| {
+            let at = (y * width + x) * 3;
+            pixels[at..at + 3].copy_from_slice(&[r, g, b]);
+        };
+
+        for y in 0..self.rows() {
+            for x in 0..self.cols() {
+                let colour = self.ground(x, y);
+                for py in y * scale..(y + 1) * scale {
+                    for px in x * scale..(x + 1) * scale {
+                        put(px, py, colour);
+                    }
+                }
+            }
+        }
+
+        // The line is half a square thick, at least one pixel.
+        let thickness = (scale / 2).max(1);
+        let offset = (scale - thickness) / 2;
+        let mut line = |(x0, y0): (usize, usize), (x1, y1): (usize, usize), colour: Rgb| {
+            let (left, right) = (x0.min(x1) * scale + offset, x0.max(x1) * scale + offset + thickness);
+            let (top, bottom) = (y0.min(y1) * scale + offset, y0.max(y1) * scale + offset + thickness);
+            for py in top..bottom {
+                for px in left..right {
+                    put(px, py, colour);
+                }
+            }
+        };
+        for step in self.path.windows(2) {
+            line(step[0], step[1], ROUTE);
+        }
+        if let (Some(&first), Some(&last)) = (self.path.first(), self.path.last()) {
+            line(first, first, START);
+            line(last, last, END);
+        }
+        (width, height, pixels)
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W, scale: usize) -> io::Result<()> {
+        let (width, height, pixels) = self.pixels(scale);
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&pixels)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+
+    #[test]
+    fn test_arrows() {
+        let path = [(0, 0), (0, 1), (1, 1), (2, 1), (2, 0), (3, 0)];
+        assert_eq!(arrows(4, 3, &path), "v.>E\n>>^.\n....\n");
+        assert_eq!(arrows(2, 1, &[]), "..\n");
+    }
+
+    #[test]
+    fn test_terrain() {
+        assert_eq!(terrain(0), TERRAIN[0]);
+        assert_eq!(terrain(25), TERRAIN[3]);
+        assert_eq!(terrain(99), TERRAIN[3]);
+        assert_eq!(shade((100, 100, 100), 0.0), (100, 100, 100));
+        assert_eq!(shade((100, 0, 200), 0.5), (178, 128, 228));
+        assert_eq!(shade((100, 0, 200), -0.5), (50, 0, 100));
+    }
+
+    #[test]
+    fn test_png() {
+        let height = vec![vec![0, 0, 0], vec![0, 5, 0]];
+        let path = [(0, 0), (1, 0), (2, 0)];
+        let mut bytes = Vec::new();
+        Terrain::new(&height, &path).write_png(&mut bytes, 6).unwrap();
+
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (18, 12));
+        let at = |x: usize, y: usize| { let i = (y * 18 + x) * 3; (pixels[i], pixels[i + 1], pixels[i + 2]) };
+        assert_eq!(at(2, 2), START);
+        assert_eq!(at(15, 2), END);
+        assert_eq!(at(9, 2), ROUTE);
+        assert_eq!(at(9, 0), terrain(0));
+        // The bump faces the light; flat ground is left as it is.
+        assert_eq!(at(7, 7), shade(terrain(5), 0.45));
+        assert_eq!(at(13, 7), terrain(0));
+    }
+}
@@ -0,0 +1,183 @@
+//! Reads the blocky capital letters that Advent of Code puzzles draw on screens.
+//!
+//! Letters are 4 pixels wide and 6 tall, with a blank column after each, so letter `n`
+//! starts at column `n * CHAR_WIDTH`. Only the letters that have turned up in puzzles are
+//! known, except `Y`, which is drawn five pixels wide and so doesn't fit the grid; anything
+//! else comes back as an error with a picture of what was there.
+
+use std::fmt;
+
+pub const GLYPH_WIDTH : usize = 4;
+pub const GLYPH_HEIGHT : usize = 6;
+/// A glyph plus the blank column between letters.
+pub const CHAR_WIDTH : usize = GLYPH_WIDTH + 1;
+
+pub type Glyph = [[bool; GLYPH_WIDTH]; GLYPH_HEIGHT];
+
+const FONT : [(char, [&str; GLYPH_HEIGHT]); 17] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+fn glyph_from_rows(rows: &[&str; GLYPH_HEIGHT]) -> Glyph {
+    let mut glyph = [[false; GLYPH_WIDTH]; GLYPH_HEIGHT];
+    for (row, text) in glyph.iter_mut().zip(rows) {
+        for (pixel, c) in row.iter_mut().zip(text.chars()) {
+            *pixel = c == '#';
+        }
+    }
+    glyph
+}
+
+/// The letter drawn by `glyph`, if it's one we know. A blank glyph is a space.
+pub fn recognise(glyph: &Glyph) -> Option<char> {
+    if glyph.iter().flatten().all(|&lit| !lit) {
+        return Some(' ');
+    }
+    FONT.iter().find(|(_, rows)| glyph_from_rows(rows) == *glyph).map(|&(c, _)| c)
+}
+
+/// A glyph we couldn't read, and which letter of the line it was.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownGlyph {
+    pub index : usize,
+    pub glyph : Glyph
+}
+
+impl fmt::Display for UnknownGlyph {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "unknown glyph at letter {}:", self.index + 1)?;
+        for row in &self.glyph {
+            writeln!(f, "{}", row.iter().map(|&lit| if lit { '#' } else { '.' }).collect::<String>())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OcrError {
+    /// Letters are exactly `GLYPH_HEIGHT` rows tall.
+    WrongHeight(usize),
+    /// Some letters weren't recognised. `text` has a `?` in place of each of them.
+    UnknownGlyphs { text: String, unknown: Vec<UnknownGlyph> }
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OcrError::WrongHeight(rows) => write!(f, "letters are {} rows tall, not {}", GLYPH_HEIGHT, rows),
+            OcrError::UnknownGlyphs { text, unknown } => {
+                writeln!(f, "couldn't read all of '{}'", text)?;
+                unknown.iter().try_for_each(|glyph| write!(f, "{}", glyph))
+            }
+        }
+    }
+}
+
+impl std::error::Error for OcrError {}
+
+/// Read a line of letters from rows of pixels, such as a day 10 framebuffer.
+pub fn read<R: AsRef<[bool]>>(rows: &[R]) -> Result<String, OcrError> {
+    if rows.len() != GLYPH_HEIGHT {
+        return Err(OcrError::WrongHeight(rows.len()));
+    }
+    let width = rows.iter().map(|row| row.as_ref().len()).max().unwrap_or(0);
+    let mut text = String::new();
+    let mut unknown = Vec::new();
+    for index in 0..width.div_ceil(CHAR_WIDTH) {
+        let mut glyph = [[false; GLYPH_WIDTH]; GLYPH_HEIGHT];
+        for (glyph_row, row) in glyph.iter_mut().zip(rows) {
+            for (x, pixel) in glyph_row.iter_mut().enumerate() {
+                *pixel = row.as_ref().get(index * CHAR_WIDTH + x).copied().unwrap_or(false);
+            }
+        }
+        match recognise(&glyph) {
+            Some(c) => text.push(c),
+            None => {
+                text.push('?');
+                unknown.push(UnknownGlyph { index, glyph });
+            }
+        }
+    }
+    let text = text.trim_end().to_string();
+    if unknown.is_empty() { Ok(text) } else { Err(OcrError::UnknownGlyphs { text, unknown }) }
+}
+
+/// Read letters drawn with `#` for lit pixels and anything else for dark ones.
+pub fn read_text(picture: &str) -> Result<String, OcrError> {
+    let rows : Vec<Vec<bool>> = picture.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.chars().map(|c| c == '#').collect())
+        .collect();
+    read(&rows)
+}
+
+#[cfg(test)]
+ mod test {
+    use super::*;
+
+    #[test]
+    fn test_read() {
+        let screen = "
+###...##..###..###..#..#..##..###....##.
+#..#.#..#.#..#.#..#.#.#..#..#.#..#....#.
+#..#.#....#..#.###..##...#..#.#..#....#.
+###..#....###..#..#.#.#..####.###.....#.
+#....#..#.#....#..#.#.#..#..#.#....#..#.
+#.....##..#....###..#..#.#..#.#.....##..
+";
+        assert_eq!(read_text(screen), Ok("PCPBKAPJ".to_string()));
+
+        let rows : Vec<[bool; 40]> = screen.lines().skip(1)
+            .map(|line| std::array::from_fn(|x| line.as_bytes()[x] == b'#'))
+            .collect();
+        assert_eq!(read(&rows), Ok("PCPBKAPJ".to_string()));
+    }
+
+    #[test]
+    fn test_whole_font() {
+        let mut rows = vec![String::new(); GLYPH_HEIGHT];
+        for (_, glyph) in FONT {
+            for (row, text) in rows.iter_mut().zip(glyph) {
+                *row += text;
+                *row += " ";
+            }
+        }
+        assert_eq!(read_text(&rows.join("\n")), Ok("ABCEFGHIJKLOPRSUZ".to_string()));
+    }
+
+    #[test]
+    fn test_unknown() {
+        let screen = "
+#..#.#...
+#..#.#...
+####.#...
+#..#.###.
+#..#.#..#
+#..#.###.";
+        let error = read_text(screen).unwrap_err();
+        let OcrError::UnknownGlyphs { text, unknown } = &error else { panic!("{:?}", error) };
+        assert_eq!(text, "H?");
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].index, 1);
+        assert_eq!(unknown[0].to_string(), "unknown glyph at letter 2:\n#...\n#...\n#...\n###.\n#..#\n###.\n");
+
+        assert_eq!(read_text("#..#\n####"), Err(OcrError::WrongHeight(2)));
+        assert_eq!(read_text("....\n....\n....\n....\n....\n...."), Ok(String::new()));
+    }
+}